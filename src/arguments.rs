@@ -0,0 +1,140 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsString;
+
+use anyhow::anyhow;
+
+/// Values of the `${name}` placeholders in the launch arguments.
+#[derive(Debug, Default)]
+pub struct Placeholders {
+    values: HashMap<&'static str, OsString>,
+}
+
+impl Placeholders {
+    /// Set the value of a placeholder.
+    pub fn set<V: Into<OsString>>(&mut self, name: &'static str, value: V) -> &mut Self {
+        self.values.insert(name, value.into());
+        self
+    }
+
+    /// Replace the placeholders in every argument.
+    ///
+    /// Each argument stays a single argument, whatever the values contain.
+    /// Fails with a list of the unknown placeholders if there are any.
+    pub fn substitute<'a, I>(&self, args: I) -> anyhow::Result<Vec<OsString>>
+    where
+        I: IntoIterator<Item = &'a String>,
+    {
+        let mut result = vec![];
+        let mut unknown = BTreeSet::new();
+
+        for arg in args {
+            let mut out = OsString::new();
+            let mut rest = arg.as_str();
+
+            while let Some(start) = rest.find("${") {
+                // a `${` without a closing brace is kept as it is
+                let end = match rest[start..].find('}') {
+                    Some(end) => start + end,
+                    None => break,
+                };
+
+                out.push(&rest[..start]);
+
+                let name = &rest[start + 2..end];
+                match self.values.get(name) {
+                    Some(value) => out.push(value),
+                    None => {
+                        unknown.insert(format!("${{{name}}}"));
+                    }
+                }
+
+                rest = &rest[end + 1..];
+            }
+
+            out.push(rest);
+            result.push(out);
+        }
+
+        if !unknown.is_empty() {
+            return Err(anyhow!(
+                "Unknown placeholders in the launch arguments: {unknown}",
+                unknown = unknown.into_iter().collect::<Vec<_>>().join(", ")
+            ));
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn placeholders() -> Placeholders {
+        let mut placeholders = Placeholders::default();
+        placeholders
+            .set("auth_player_name", "Big Name")
+            .set("game_directory", "/home/steve/my games/.minecraft")
+            .set("launcher_name", "watercraft")
+            .set("launcher_version", "0.1.0");
+        placeholders
+    }
+
+    #[test]
+    fn values_with_spaces_stay_one_argument() {
+        let args = args(&[
+            "--username",
+            "${auth_player_name}",
+            "--gameDir",
+            "${game_directory}",
+        ]);
+
+        assert_eq!(
+            placeholders().substitute(&args).unwrap(),
+            [
+                "--username",
+                "Big Name",
+                "--gameDir",
+                "/home/steve/my games/.minecraft"
+            ]
+        );
+    }
+
+    #[test]
+    fn several_placeholders_in_one_argument() {
+        let args = args(&["-Dminecraft.launcher.brand=${launcher_name}-${launcher_version}!"]);
+
+        assert_eq!(
+            placeholders().substitute(&args).unwrap(),
+            ["-Dminecraft.launcher.brand=watercraft-0.1.0!"]
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_are_listed() {
+        let args = args(&[
+            "${user_type}",
+            "${launcher_name}",
+            "${clientid}:${user_type}",
+        ]);
+
+        assert_eq!(
+            placeholders().substitute(&args).unwrap_err().to_string(),
+            "Unknown placeholders in the launch arguments: ${clientid}, ${user_type}"
+        );
+    }
+
+    #[test]
+    fn unterminated_placeholder_is_kept() {
+        let args = args(&["${launcher_name}-${launcher_version", "${"]);
+
+        assert_eq!(
+            placeholders().substitute(&args).unwrap(),
+            ["watercraft-${launcher_version", "${"]
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::colors;
+use crate::config::{assets_dir, assets_indexes_dir, assets_objects_dir, assets_virtual_dir};
+use crate::hash;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AssetsIndex {
+    pub objects: HashMap<String, AssetsIndexObject>,
+    /// Legacy indexes, the game reads the objects by name from `assets/virtual/{index}`.
+    #[serde(default, rename = "virtual")]
+    pub is_virtual: bool,
+    /// Pre-1.6 indexes, the game reads the objects by name from `{game directory}/resources`.
+    #[serde(default)]
+    pub map_to_resources: bool,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AssetsIndexObject {
+    pub hash: String,
+    pub size: u64,
+}
+
+impl AssetsIndex {
+    /// Read the assets index with the given id from the indexes directory.
+    pub fn load(id: &str) -> anyhow::Result<Self> {
+        let file = File::open(format!("{dir}/{id}.json", dir = assets_indexes_dir!()))?;
+
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Get the directory passed to the game as `${game_assets}`.
+    ///
+    /// For legacy and pre-1.6 indexes the directory tree is created from the object store if needed.
+    pub fn game_assets(&self, id: &str, game_dir: &str) -> anyhow::Result<String> {
+        let dir = if self.map_to_resources {
+            format!("{game_dir}/resources")
+        } else if self.is_virtual {
+            assets_virtual_dir!(id)
+        } else {
+            return Ok(assets_dir!());
+        };
+
+        self.materialize(&dir)?;
+
+        Ok(dir)
+    }
+
+    /// Place every object in the given directory under its name, hard linked from the object store where possible.
+    pub fn materialize(&self, dir: &str) -> anyhow::Result<()> {
+        let objects_dir = assets_objects_dir!();
+
+        for (name, object) in &self.objects {
+            let target = Path::new(dir).join(name);
+
+            // skip objects that are already in place
+            if fs::metadata(&target).is_ok_and(|x| x.len() == object.size) {
+                continue;
+            }
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            if target.exists() {
+                fs::remove_file(&target)?;
+            }
+
+            let source = format!("{objects_dir}/{path}", path = object_path(&object.hash));
+
+            // hard links don't work across file systems, copy the object then
+            if fs::hard_link(&source, &target).is_err() {
+                fs::copy(&source, &target)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Path of an object in the store, relative to the objects directory (`{hash[0..2]}/{hash}`).
+pub fn object_path(hash: &str) -> String {
+    format!("{id}/{hash}", id = &hash[..2])
+}
+
+/// Check if the given directory name is a hash prefix directory of the object store.
+pub fn is_prefix_dir(name: &str) -> bool {
+    name.len() == 2 && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Move objects stored under their logical name (e.g. `minecraft/sounds/...`) into the hash based layout.
+///
+/// Returns the number of migrated files.
+pub fn migrate_objects() -> anyhow::Result<usize> {
+    let objects_dir = assets_objects_dir!();
+
+    if !Path::new(&objects_dir).exists() {
+        return Ok(0);
+    }
+
+    let mut migrated = 0;
+
+    for entry in fs::read_dir(&objects_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // the hash prefix directories are already in the right layout
+        if entry.file_type()?.is_dir() && is_prefix_dir(&name) {
+            continue;
+        }
+
+        migrated += migrate_tree(&entry.path(), &objects_dir)?;
+    }
+
+    if migrated > 0 {
+        println!(
+            "{GREEN}Migrated {migrated} assets objects to the hash based layout{RESET}",
+            GREEN = colors::GREEN,
+            RESET = colors::RESET
+        );
+    }
+
+    Ok(migrated)
+}
+
+/// Move every file under the given path to the object store and remove the emptied directories.
+fn migrate_tree(path: &Path, objects_dir: &str) -> anyhow::Result<usize> {
+    if path.is_dir() {
+        let mut migrated = 0;
+
+        for entry in fs::read_dir(path)? {
+            migrated += migrate_tree(&entry?.path(), objects_dir)?;
+        }
+
+        fs::remove_dir(path)?;
+
+        return Ok(migrated);
+    }
+
+    let hash = hash::sha1_file(path)?;
+    let target = format!("{objects_dir}/{path}", path = object_path(&hash));
+
+    if Path::new(&target).exists() {
+        fs::remove_file(path)?;
+    } else {
+        fs::create_dir_all(format!("{objects_dir}/{id}", id = &hash[..2]))?;
+        fs::rename(path, &target)?;
+    }
+
+    Ok(1)
+}
@@ -0,0 +1,593 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::anyhow;
+use md5::{Digest, Md5};
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::config::game_dir;
+use crate::http_client::{self, request_builder, send_http};
+use crate::{colors, settings};
+
+/// Scopes of the Microsoft token, `offline_access` is needed to get a refresh token.
+const SCOPE: &str = "XboxLive.signin offline_access";
+
+/// Minecraft tokens that expire within this many seconds are refreshed before launching.
+const REFRESH_MARGIN: u64 = 300;
+
+/// A Microsoft account that signed in, stored in `~/.minecraft/accounts.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    /// Minecraft username.
+    pub name: String,
+    /// Minecraft profile UUID, without dashes.
+    pub uuid: String,
+    /// Xbox user ID.
+    pub xuid: String,
+    /// Minecraft access token.
+    pub access_token: String,
+    /// Unix time the access token expires at.
+    pub expires_at: u64,
+    /// Microsoft refresh token, used to get new tokens without signing in again.
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Accounts {
+    accounts: Vec<Account>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeviceCode {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    expires_in: u64,
+    #[serde(default = "default_interval")]
+    interval: u64,
+}
+#[derive(Debug, Deserialize)]
+struct MicrosoftToken {
+    access_token: String,
+    refresh_token: String,
+}
+#[derive(Debug, Deserialize)]
+struct MicrosoftError {
+    error: String,
+    error_description: Option<String>,
+}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XboxToken {
+    token: String,
+    display_claims: XboxDisplayClaims,
+}
+#[derive(Debug, Deserialize)]
+struct XboxDisplayClaims {
+    xui: Vec<XboxUser>,
+}
+#[derive(Debug, Deserialize)]
+struct XboxUser {
+    uhs: String,
+    xid: Option<String>,
+}
+#[derive(Debug, Deserialize)]
+struct XboxError {
+    #[serde(rename = "XErr")]
+    xerr: u64,
+}
+#[derive(Debug, Deserialize)]
+struct MinecraftToken {
+    access_token: String,
+    expires_in: u64,
+}
+#[derive(Debug, Deserialize)]
+struct Entitlements {
+    items: Vec<serde_json::Value>,
+}
+#[derive(Debug, Deserialize)]
+struct Profile {
+    id: String,
+    name: String,
+}
+
+/// Body of a request to the login services.
+enum Body<'a> {
+    None,
+    Form(&'a [(&'a str, &'a str)]),
+    Json(serde_json::Value),
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+/// Get the UUID of a player without an account, the same one the game and servers in offline mode use.
+///
+/// It's the name based (version 3) UUID of `OfflinePlayer:<username>`, without dashes like the UUIDs of accounts.
+pub fn offline_uuid(username: &str) -> String {
+    let mut hash = Md5::digest(format!("OfflinePlayer:{username}"));
+
+    // set the version (3) and the variant (RFC 4122)
+    hash[6] = hash[6] & 0x0f | 0x30;
+    hash[8] = hash[8] & 0x3f | 0x80;
+
+    format!("{hash:x}")
+}
+
+/// Sign in with a Microsoft account using a device code and save the account.
+pub async fn login() -> anyhow::Result<()> {
+    let auth = &settings::get().auth;
+    let client_id = client_id()?;
+
+    let (status, body) = send(
+        Method::POST,
+        &auth.device_code_url,
+        Body::Form(&[("client_id", client_id), ("scope", SCOPE)]),
+        None,
+    )
+    .await?;
+    let device_code = parse::<DeviceCode>(&auth.device_code_url, status, &body)?;
+
+    println!(
+        "{MAGENTA}Open {GREEN}{uri}{MAGENTA} and enter the code {GREEN}{code}{RESET}",
+        uri = device_code.verification_uri,
+        code = device_code.user_code,
+        MAGENTA = colors::MAGENTA,
+        GREEN = colors::GREEN,
+        RESET = colors::RESET
+    );
+
+    let token = poll(client_id, &device_code).await?;
+    let account = minecraft_account(token).await?;
+
+    // signing in again replaces the saved account
+    let mut accounts = load()?;
+    accounts.accounts.retain(|x| x.uuid != account.uuid);
+    accounts.accounts.push(account.clone());
+    save(&accounts)?;
+
+    println!(
+        "{GREEN}Logged in as {name}, launch the game with `watercraft launch <version> {name}`{RESET}",
+        name = account.name,
+        GREEN = colors::GREEN,
+        RESET = colors::RESET
+    );
+
+    Ok(())
+}
+
+/// Remove a saved account.
+pub fn logout(name: &str) -> anyhow::Result<()> {
+    let mut accounts = load()?;
+    let count = accounts.accounts.len();
+
+    accounts
+        .accounts
+        .retain(|x| !x.name.eq_ignore_ascii_case(name));
+
+    if accounts.accounts.len() == count {
+        return Err(anyhow!("There is no account named {name}"));
+    }
+
+    save(&accounts)?;
+
+    println!(
+        "{GREEN}Logged out {name}{RESET}",
+        GREEN = colors::GREEN,
+        RESET = colors::RESET
+    );
+
+    Ok(())
+}
+
+/// Get the saved account with the given name, its tokens are refreshed if the Minecraft token (almost) expired.
+pub async fn account(name: &str) -> anyhow::Result<Option<Account>> {
+    let mut accounts = load()?;
+
+    let index = match accounts
+        .accounts
+        .iter()
+        .position(|x| x.name.eq_ignore_ascii_case(name))
+    {
+        Some(index) => index,
+        None => return Ok(None),
+    };
+
+    let account = &accounts.accounts[index];
+
+    if account.expires_at > now() + REFRESH_MARGIN {
+        return Ok(Some(account.clone()));
+    }
+
+    // singleplayer works with an expired token, only online servers need a valid one
+    if settings::get().offline {
+        println!(
+            "{RED}The token of {name} expired and can't be refreshed in offline mode{RESET}",
+            name = account.name,
+            RED = colors::RED,
+            RESET = colors::RESET
+        );
+
+        return Ok(Some(account.clone()));
+    }
+
+    let token = refresh(&account.refresh_token).await?;
+    let account = minecraft_account(token).await?;
+
+    accounts.accounts[index] = account.clone();
+    save(&accounts)?;
+
+    Ok(Some(account))
+}
+
+/// Get the client ID of the Azure application used to sign in.
+fn client_id() -> anyhow::Result<&'static str> {
+    settings::get().auth.client_id.as_deref().ok_or_else(|| {
+        anyhow!("Set client_id in the [auth] section of watercraft.toml to the client ID of an Azure application to sign in with Microsoft")
+    })
+}
+
+/// Wait until the user entered the device code and get the Microsoft token.
+async fn poll(client_id: &str, device_code: &DeviceCode) -> anyhow::Result<MicrosoftToken> {
+    let token_url = &settings::get().auth.token_url;
+    let deadline = Instant::now() + Duration::from_secs(device_code.expires_in);
+    let mut interval = device_code.interval;
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+
+        if Instant::now() > deadline {
+            return Err(anyhow!("The code expired, run `watercraft login` again"));
+        }
+
+        let (status, body) = send(
+            Method::POST,
+            token_url,
+            Body::Form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                ("client_id", client_id),
+                ("device_code", &device_code.device_code),
+            ]),
+            None,
+        )
+        .await?;
+
+        if status.is_success() {
+            return parse(token_url, status, &body);
+        }
+
+        let error = parse::<MicrosoftError>(token_url, StatusCode::OK, &body)?;
+        interval = next_interval(error, interval)?;
+    }
+}
+
+/// Get the seconds to wait before polling again after the token request failed with the given error.
+///
+/// The sign in failed if the error isn't one of the errors of a pending sign in.
+fn next_interval(error: MicrosoftError, interval: u64) -> anyhow::Result<u64> {
+    match error.error.as_str() {
+        "authorization_pending" => Ok(interval),
+        "slow_down" => Ok(interval + 5),
+        "authorization_declined" => Err(anyhow!("The sign in was declined")),
+        "expired_token" => Err(anyhow!("The code expired, run `watercraft login` again")),
+        _ => Err(anyhow!(
+            "Sign in failed: {error}",
+            error = error.error_description.unwrap_or(error.error)
+        )),
+    }
+}
+
+/// Get a new Microsoft token with a refresh token.
+async fn refresh(refresh_token: &str) -> anyhow::Result<MicrosoftToken> {
+    let token_url = &settings::get().auth.token_url;
+
+    let (status, body) = send(
+        Method::POST,
+        token_url,
+        Body::Form(&[
+            ("grant_type", "refresh_token"),
+            ("client_id", client_id()?),
+            ("scope", SCOPE),
+            ("refresh_token", refresh_token),
+        ]),
+        None,
+    )
+    .await?;
+
+    if !status.is_success() {
+        return Err(anyhow!(
+            "The account has to sign in again with `watercraft login`, refreshing its token failed: {body}",
+            body = String::from_utf8_lossy(&body)
+        ));
+    }
+
+    parse(token_url, status, &body)
+}
+
+/// Sign in to Xbox Live and Minecraft with a Microsoft token and get the Minecraft account.
+async fn minecraft_account(token: MicrosoftToken) -> anyhow::Result<Account> {
+    let auth = &settings::get().auth;
+
+    // Xbox Live user token
+    let xbox_live = xbox(
+        &auth.xbox_live_url,
+        json!({
+            "Properties": {
+                "AuthMethod": "RPS",
+                "SiteName": "user.auth.xboxlive.com",
+                "RpsTicket": format!("d={token}", token = token.access_token)
+            },
+            "RelyingParty": "http://auth.xboxlive.com",
+            "TokenType": "JWT"
+        }),
+    )
+    .await?;
+
+    // XSTS tokens for the Minecraft services and for Xbox Live, only the second one has the Xbox user ID
+    let xsts = |relying_party: &str| {
+        json!({
+            "Properties": {
+                "SandboxId": "RETAIL",
+                "UserTokens": [xbox_live.token]
+            },
+            "RelyingParty": relying_party,
+            "TokenType": "JWT"
+        })
+    };
+    let minecraft_xsts = xbox(&auth.xsts_url, xsts("rp://api.minecraftservices.com/")).await?;
+    let xbox_xsts = xbox(&auth.xsts_url, xsts("http://xboxlive.com")).await?;
+
+    let user_hash = &minecraft_xsts
+        .display_claims
+        .xui
+        .first()
+        .ok_or_else(|| anyhow!("The XSTS token has no user hash"))?
+        .uhs;
+    let xuid = xbox_xsts
+        .display_claims
+        .xui
+        .into_iter()
+        .find_map(|x| x.xid)
+        .unwrap_or_default();
+
+    // Minecraft access token
+    let (status, body) = send(
+        Method::POST,
+        &auth.minecraft_login_url,
+        Body::Json(json!({
+            "identityToken": format!("XBL3.0 x={user_hash};{token}", token = minecraft_xsts.token)
+        })),
+        None,
+    )
+    .await?;
+    let minecraft = parse::<MinecraftToken>(&auth.minecraft_login_url, status, &body)?;
+
+    // the account has to own the game
+    let (status, body) = send(
+        Method::GET,
+        &auth.entitlements_url,
+        Body::None,
+        Some(&minecraft.access_token),
+    )
+    .await?;
+    let entitlements = parse::<Entitlements>(&auth.entitlements_url, status, &body)?;
+
+    if entitlements.items.is_empty() {
+        return Err(anyhow!("The account doesn't own Minecraft"));
+    }
+
+    let (status, body) = send(
+        Method::GET,
+        &auth.profile_url,
+        Body::None,
+        Some(&minecraft.access_token),
+    )
+    .await?;
+
+    if status == StatusCode::NOT_FOUND {
+        return Err(anyhow!(
+            "The account has no Minecraft profile yet, create one on minecraft.net first"
+        ));
+    }
+
+    let profile = parse::<Profile>(&auth.profile_url, status, &body)?;
+
+    Ok(Account {
+        name: profile.name,
+        uuid: profile.id,
+        xuid,
+        access_token: minecraft.access_token,
+        expires_at: now() + minecraft.expires_in,
+        refresh_token: token.refresh_token,
+    })
+}
+
+/// Request an Xbox Live or XSTS token.
+async fn xbox(url: &str, request: serde_json::Value) -> anyhow::Result<XboxToken> {
+    let (status, body) = send(Method::POST, url, Body::Json(request), None).await?;
+
+    if let Some(error) = xbox_error(status, &body) {
+        return Err(error);
+    }
+
+    parse(url, status, &body)
+}
+
+/// Get the reason an Xbox Live or XSTS request was refused, XSTS explains it with an error code.
+fn xbox_error(status: StatusCode, body: &[u8]) -> Option<anyhow::Error> {
+    if status != StatusCode::UNAUTHORIZED {
+        return None;
+    }
+
+    let code = serde_json::from_slice::<XboxError>(body).ok()?.xerr;
+    let reason = match code {
+        2148916233 => {
+            "The Microsoft account has no Xbox account, sign in on xbox.com once to create it"
+        }
+        2148916235 => "Xbox Live is not available in the country of the account",
+        2148916236 | 2148916237 => "The account needs adult verification on xbox.com",
+        2148916238 => "The account belongs to a child, it has to be added to a family by an adult",
+        _ => "Xbox Live refused the account",
+    };
+
+    Some(anyhow!("{reason} (error {code})"))
+}
+
+/// Send a request to a login service and get the status and body of the response.
+///
+/// Unlike downloads, the requests aren't retried or sent to mirrors, they carry the tokens of the account.
+/// Error responses are returned to be handled by the caller.
+async fn send(
+    method: Method,
+    url: &str,
+    body: Body<'_>,
+    bearer: Option<&str>,
+) -> anyhow::Result<(StatusCode, Vec<u8>)> {
+    if settings::get().offline {
+        return Err(anyhow!("Can't sign in to {url} in offline mode"));
+    }
+
+    let mut req = request_builder!(method, url, "Accept" => "application/json");
+
+    req = match body {
+        Body::None => req,
+        Body::Form(form) => req.form(form),
+        Body::Json(json) => req.json(&json),
+    };
+
+    if let Some(token) = bearer {
+        req = req.bearer_auth(token);
+    }
+
+    let res = http_client::timeout(send_http!(req.build()?))
+        .await
+        .map_err(|e| anyhow!("Request to {url} failed: {e}"))?;
+    let status = res.status();
+
+    Ok((status, http_client::timeout(res.bytes()).await?.to_vec()))
+}
+
+/// Parse a successful JSON response.
+fn parse<T: DeserializeOwned>(url: &str, status: StatusCode, body: &[u8]) -> anyhow::Result<T> {
+    if !status.is_success() {
+        return Err(anyhow!(
+            "{url} responded with {status}: {body}",
+            body = String::from_utf8_lossy(body)
+        ));
+    }
+
+    serde_json::from_slice(body).map_err(|e| anyhow!("Failed to parse the response of {url}: {e}"))
+}
+
+/// Read the saved accounts.
+fn load() -> anyhow::Result<Accounts> {
+    let path = game_dir!("accounts.json");
+
+    if !Path::new(&path).exists() {
+        return Ok(Accounts::default());
+    }
+
+    serde_json::from_slice(&fs::read(&path)?).map_err(|e| anyhow!("Failed to parse {path}: {e}"))
+}
+
+/// Save the accounts, the file is only readable by the user because it contains the tokens.
+fn save(accounts: &Accounts) -> anyhow::Result<()> {
+    let path = game_dir!("accounts.json");
+    let tmp = format!("{path}.part");
+
+    fs::create_dir_all(game_dir!())?;
+    fs::write(&tmp, serde_json::to_vec_pretty(accounts)?)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(0o600))?;
+    }
+
+    fs::rename(&tmp, &path)?;
+
+    Ok(())
+}
+
+/// Get the current Unix time in seconds.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_uuid_matches_the_game() {
+        assert_eq!(offline_uuid("Notch"), "b50ad385829d3141a2167e7d7539ba7f");
+        assert_ne!(offline_uuid("Notch"), offline_uuid("jeb_"));
+    }
+
+    fn error(error: &str) -> MicrosoftError {
+        MicrosoftError {
+            error: error.to_string(),
+            error_description: None,
+        }
+    }
+
+    #[test]
+    fn pending_sign_in_keeps_polling() {
+        assert_eq!(next_interval(error("authorization_pending"), 5).unwrap(), 5);
+        assert_eq!(next_interval(error("slow_down"), 5).unwrap(), 10);
+    }
+
+    #[test]
+    fn failed_sign_in_stops_polling() {
+        let declined = next_interval(error("authorization_declined"), 5).unwrap_err();
+        assert_eq!(declined.to_string(), "The sign in was declined");
+
+        let expired = next_interval(error("expired_token"), 5).unwrap_err();
+        assert!(expired.to_string().starts_with("The code expired"));
+
+        let other = MicrosoftError {
+            error: "invalid_client".to_string(),
+            error_description: Some("Unknown client".to_string()),
+        };
+        assert_eq!(
+            next_interval(other, 5).unwrap_err().to_string(),
+            "Sign in failed: Unknown client"
+        );
+    }
+
+    #[test]
+    fn xsts_error_codes() {
+        let child = xbox_error(
+            StatusCode::UNAUTHORIZED,
+            br#"{"Identity":"0","XErr":2148916238}"#,
+        );
+        assert_eq!(
+            child.unwrap().to_string(),
+            "The account belongs to a child, it has to be added to a family by an adult (error 2148916238)"
+        );
+
+        let no_xbox = xbox_error(StatusCode::UNAUTHORIZED, br#"{"XErr":2148916233}"#);
+        assert!(no_xbox.unwrap().to_string().contains("no Xbox account"));
+
+        let unknown = xbox_error(StatusCode::UNAUTHORIZED, br#"{"XErr":1}"#);
+        assert_eq!(
+            unknown.unwrap().to_string(),
+            "Xbox Live refused the account (error 1)"
+        );
+    }
+
+    #[test]
+    fn other_xbox_responses_are_not_xsts_errors() {
+        assert!(xbox_error(StatusCode::OK, br#"{"XErr":2148916238}"#).is_none());
+        assert!(xbox_error(StatusCode::UNAUTHORIZED, b"").is_none());
+    }
+}
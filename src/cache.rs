@@ -0,0 +1,194 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use reqwest::header::{HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::config::cache_dir;
+use crate::http_client::{self, build_request, send_http};
+use crate::{colors, hash, progress, settings};
+
+/// Validators of a cached response, sent back to revalidate it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CacheMeta {
+    /// The official URL the response belongs to, even if a mirror sent it.
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Get the JSON document at the given URL through the metadata cache.
+///
+/// The cached copy is revalidated with `If-None-Match`/`If-Modified-Since` and used as is in offline mode
+/// or if the server can't be reached.
+pub async fn get_json<T: DeserializeOwned>(url: &str, name: &str) -> anyhow::Result<T> {
+    let body = get(url, name).await?;
+
+    serde_json::from_slice(&body).map_err(|e| anyhow!("Failed to parse {url}: {e}"))
+}
+
+/// Get the JSON document at the given URL through the metadata cache, as it was sent by the server.
+///
+/// See [`get_json`] for how the cache is used.
+pub async fn get(url: &str, name: &str) -> anyhow::Result<Vec<u8>> {
+    let path = format!("{cache_dir}/{name}", cache_dir = cache_dir!());
+    let meta_path = format!("{path}.meta");
+
+    let cached = Path::new(&path).exists();
+
+    if settings::get().offline {
+        if !cached {
+            return Err(anyhow!(
+                "{url} is not cached, it can't be downloaded in offline mode"
+            ));
+        }
+
+        return read(&path);
+    }
+
+    // the validators of another URL (like an older version JSON) say nothing about this one
+    let meta = match fs::read_to_string(&meta_path) {
+        Ok(meta) if cached => serde_json::from_str::<CacheMeta>(&meta)
+            .ok()
+            .filter(|meta| meta.url == url)
+            .unwrap_or_default(),
+        _ => CacheMeta::default(),
+    };
+
+    let res = http_client::request(url, |source| {
+        let meta = &meta;
+
+        async move {
+            let mut req = build_request!(Method::GET, &source)?;
+
+            // the validators belong to the official URL, they're sent to its mirrors as well
+            if let Some(etag) = &meta.etag {
+                req.headers_mut()
+                    .insert(IF_NONE_MATCH, HeaderValue::from_str(etag)?);
+            }
+            if let Some(last_modified) = &meta.last_modified {
+                req.headers_mut()
+                    .insert(IF_MODIFIED_SINCE, HeaderValue::from_str(last_modified)?);
+            }
+
+            let res = http_client::timeout(send_http!(req))
+                .await?
+                .error_for_status()?;
+
+            if res.status() == StatusCode::NOT_MODIFIED {
+                return Ok(None);
+            }
+
+            let header = |name| {
+                res.headers()
+                    .get(name)
+                    .and_then(|x: &HeaderValue| x.to_str().ok())
+                    .map(|x| x.to_string())
+            };
+            let meta = CacheMeta {
+                url: url.to_string(),
+                etag: header(ETAG),
+                last_modified: header(LAST_MODIFIED),
+            };
+
+            let body = http_client::timeout(res.bytes()).await?;
+
+            Ok(Some((body, meta)))
+        }
+    })
+    .await;
+
+    match res {
+        // not modified, the cached copy is still valid
+        Ok(None) => read(&path),
+        Ok(Some((body, meta))) => {
+            // don't replace the cached copy with a broken document
+            serde_json::from_slice::<serde_json::Value>(&body)
+                .map_err(|e| anyhow!("Failed to parse {url}: {e}"))?;
+
+            write(&path, &body)?;
+            write(&meta_path, serde_json::to_string(&meta)?.as_bytes())?;
+
+            Ok(body.to_vec())
+        }
+        Err(e) if cached => {
+            progress::println(&format!(
+                "{RED}{e}, using the cached copy{RESET}",
+                RED = colors::RED,
+                RESET = colors::RESET
+            ));
+
+            read(&path)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Get the JSON document at the given URL with a known SHA-1 hash and size through the metadata cache.
+///
+/// The cached copy is used as long as it matches. A source that sends other bytes counts as failed,
+/// so the next mirror or the URL itself is tried.
+pub async fn get_json_verified<T: DeserializeOwned>(
+    url: &str,
+    name: &str,
+    sha1: &str,
+    size: u64,
+) -> anyhow::Result<T> {
+    let path = format!("{cache_dir}/{name}", cache_dir = cache_dir!());
+
+    let body = match fs::read(&path) {
+        Ok(body) if hash::sha1_bytes(&body).eq_ignore_ascii_case(sha1) => body,
+        _ if settings::get().offline => {
+            return Err(anyhow!(
+                "{url} is not cached, it can't be downloaded in offline mode"
+            ));
+        }
+        _ => {
+            let body = http_client::request(url, |url| async move {
+                let res = http_client::timeout(send_http!(Method::GET, &url))
+                    .await?
+                    .error_for_status()?;
+                let body = http_client::timeout(res.bytes()).await?;
+                let hash = hash::sha1_bytes(&body);
+
+                if !hash.eq_ignore_ascii_case(sha1) || body.len() as u64 != size {
+                    return Err(anyhow!(
+                        "Checksum mismatch: expected {sha1} ({size} bytes), got {hash} ({len} bytes)",
+                        len = body.len()
+                    ));
+                }
+
+                Ok(body.to_vec())
+            })
+            .await?;
+
+            write(&path, &body)?;
+
+            body
+        }
+    };
+
+    serde_json::from_slice(&body).map_err(|e| anyhow!("Failed to parse {url}: {e}"))
+}
+
+/// Read a cached document.
+fn read(path: &str) -> anyhow::Result<Vec<u8>> {
+    fs::read(path).map_err(|e| anyhow!("Failed to read the cached {path}: {e}"))
+}
+
+/// Write a file of the cache, it's replaced atomically so a crash can't leave a truncated copy.
+fn write(path: &str, data: &[u8]) -> anyhow::Result<()> {
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tmp = format!("{path}.part");
+
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)?;
+
+    Ok(())
+}
@@ -1,5 +1,31 @@
-pub const RESET: &str = "\x1b[0m";
-pub const MAGENTA: &str = "\x1b[35m";
-pub const GREEN: &str = "\x1b[92m";
-pub const GREY: &str = "\x1b[37m";
-pub const RED: &str = "\x1b[91m";
+use std::fmt;
+use std::io::{self, IsTerminal};
+
+use once_cell::sync::Lazy;
+
+/// Colors are only written to a terminal and can be disabled with the `NO_COLOR` environment variable.
+static ENABLED: Lazy<bool> = Lazy::new(|| {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|x| !x.is_empty());
+
+    !no_color && io::stdout().is_terminal()
+});
+
+/// An ANSI escape code, written only if colors are enabled.
+#[derive(Debug, Clone, Copy)]
+pub struct Color(&'static str);
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *ENABLED {
+            f.write_str(self.0)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+pub const RESET: Color = Color("\x1b[0m");
+pub const MAGENTA: Color = Color("\x1b[35m");
+pub const GREEN: Color = Color("\x1b[92m");
+pub const GREY: Color = Color("\x1b[37m");
+pub const RED: Color = Color("\x1b[91m");
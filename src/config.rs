@@ -23,8 +23,8 @@ macro_rules! version_dir {
 }
 
 macro_rules! libraries_dir {
-    ($version: expr) => {
-        crate::config::game_dir!("versions/{version}/libraries", version = $version)
+    () => {
+        crate::config::game_dir!("libraries")
     };
 }
 
@@ -59,6 +59,24 @@ macro_rules! assets_objects_dir {
     };
 }
 
+macro_rules! assets_virtual_dir {
+    ($index: expr) => {
+        crate::config::assets_dir!(format!("virtual/{index}", index = $index))
+    };
+}
+
+macro_rules! cache_dir {
+    () => {
+        crate::config::game_dir!("cache")
+    };
+}
+
+macro_rules! runtime_dir {
+    ($component: expr) => {
+        crate::config::game_dir!("runtimes/{component}", component = $component)
+    };
+}
+
 macro_rules! profile_dir {
     ($username: expr) => {
         crate::config::game_dir!("profiles/{username}", username = $username)
@@ -68,20 +86,23 @@ macro_rules! profile_dir {
 pub(crate) use assets_dir;
 pub(crate) use assets_indexes_dir;
 pub(crate) use assets_objects_dir;
+pub(crate) use assets_virtual_dir;
+pub(crate) use cache_dir;
 pub(crate) use game_dir;
 pub(crate) use libraries_dir;
 pub(crate) use libraries_natives_dir;
 pub(crate) use profile_dir;
+pub(crate) use runtime_dir;
 pub(crate) use version_dir;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub version: String,
     pub assets_root: String,
-    pub auth_uuid: u64,
-    pub auth_access_token: u64,
-    pub clientid: u64,
-    pub auth_xuid: u64,
+    pub auth_uuid: String,
+    pub auth_access_token: String,
+    pub clientid: String,
+    pub auth_xuid: String,
     pub version_type: String,
     pub user_type: String,
     pub launcher_name: String,
@@ -91,7 +112,8 @@ pub struct Config {
     pub natives_directory: String,
     pub log_path: String,
     pub classpath: Vec<String>,
-    pub java: String,
-    pub jvm_opts: String,
-    pub game_args: String,
+    pub java_component: String,
+    pub java_major_version: u32,
+    pub jvm_opts: Vec<String>,
+    pub game_args: Vec<String>,
 }
@@ -1,354 +1,281 @@
+use crate::assets::{self, AssetsIndex};
 use crate::config::{
-    assets_dir, assets_indexes_dir, assets_objects_dir, libraries_dir, libraries_natives_dir,
-    version_dir, Config,
+    assets_indexes_dir, assets_objects_dir, assets_virtual_dir, libraries_dir,
+    libraries_natives_dir, version_dir,
 };
+use crate::rules::Environment;
+use crate::runtime::Runtime;
+use crate::version::{VersionDetails, VersionManifest, MAINLINE_VERSIONS};
+use crate::{cache, colors, libraries};
 use anyhow::anyhow;
-use reqwest::Method;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs;
-use std::fs::File;
-use std::io::Write;
+use std::path::Path;
 
-use crate::http_client::{download_and_extract, download_file, send_http};
+use crate::downloader::{self, Summary, Task};
 
-const MAINLINE_VERSIONS: &str = "https://launchermeta.mojang.com/mc/game/version_manifest.json";
 const OBJ_SERVER: &str = "https://resources.download.minecraft.net";
 
-#[cfg(target_os = "linux")]
-const OS_NAME: &str = "linux";
-#[cfg(target_os = "windows")]
-const OS_NAME: &str = "windows";
-#[cfg(target_os = "macos")]
-const OS_NAME: &str = "osx";
-
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionManifest {
-    latest: VersionManifestLatest,
-    versions: Vec<VersionManifestVersions>,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionManifestLatest {
-    release: String,
-    snapshot: String,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionManifestVersions {
-    id: String,
-    #[serde(rename = "type")]
-    typ: String,
-    url: String,
-    time: String,
-    #[serde(rename = "releaseTime")]
-    release_time: String,
-}
+/// Name of the version manifest in the metadata cache.
+const MANIFEST_CACHE: &str = "version_manifest.json";
 
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetails {
-    id: String,
-    assets: String,
-    #[serde(rename = "mainClass")]
-    main_class: String,
-    arguments: Option<VersionDetailsArguments>,
-    #[serde(rename = "minecraftArguments")]
-    minecraft_arguments: Option<String>,
-    #[serde(rename = "assetIndex")]
-    asset_index: VersionDetailsAssetIndex,
-    downloads: VersionDetailsDownloads,
-    libraries: Vec<VersionDetailsLibraries>,
-    logging: VersionDetailsLogging,
-    #[serde(rename = "releaseTime")]
-    release_time: String,
-    time: String,
-    #[serde(rename = "type")]
-    typ: String,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsArguments {
-    game: Vec<VersionDetailsArgumentsGame>,
-    // jvm
-}
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged)]
-enum VersionDetailsArgumentsGame {
-    String(String),
-    Rule(VersionDetailsArgumentsGameCustom),
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsArgumentsGameCustom {
-    rules: Vec<VersionDetailsArgumentsGameCustomRule>,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsArgumentsGameCustomRule {
-    action: String,
-    features: HashMap<String, bool>,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsDownloads {
-    client: VersionDetailsDownloadsEntry,
-    server: VersionDetailsDownloadsEntry,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsDownloadsEntry {
-    sha1: String,
-    size: u32,
-    url: String,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsLibraries {
-    name: String,
-    downloads: VersionDetailsLibrariesDownloads,
-    rules: Option<Vec<VersionDetailsLibrariesDownloadsRules>>,
-    natives: Option<HashMap<String, String>>,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsLibrariesDownloads {
-    artifact: Option<VersionDetailsLibrariesDownloadsArtifact>,
-    classifiers: Option<HashMap<String, VersionDetailsLibrariesDownloadsArtifact>>,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsLibrariesDownloadsArtifact {
-    path: String,
-    sha1: String,
-    size: u32,
-    url: String,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsLibrariesDownloadsRules {
-    action: String,
-    os: Option<VersionDetailsLibrariesDownloadsRulesOs>,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsLibrariesDownloadsRulesOs {
-    name: String,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsLogging {
-    client: VersionDetailsLoggingClient,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsLoggingClient {
-    argument: String,
-    #[serde(rename = "type")]
-    typ: String,
-    file: VersionDetailsLoggingClientFile,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsLoggingClientFile {
-    id: String,
-    sha1: String,
-    size: u32,
-    url: String,
-}
-#[derive(Debug, Serialize, Deserialize)]
-struct VersionDetailsAssetIndex {
-    id: String,
-    sha1: String,
-    size: u32,
-    #[serde(rename = "totalSize")]
-    total_size: u32,
-    url: String,
-}
+pub async fn download(version: String, jobs: usize) -> anyhow::Result<()> {
+    println!("Downloading version {version} ...");
+
+    install(version, jobs).await?;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct AssetsIndex {
-    objects: HashMap<String, AssetsIndexObject>,
+    Ok(())
 }
-#[derive(Debug, Serialize, Deserialize)]
-struct AssetsIndexObject {
-    hash: String,
-    size: u64,
+
+/// Print the versions from the version manifest, only releases unless `all` is set.
+pub async fn list_versions(all: bool) -> anyhow::Result<()> {
+    let manifest = cache::get_json::<VersionManifest>(MAINLINE_VERSIONS, MANIFEST_CACHE).await?;
+
+    for version in manifest.versions.iter().rev() {
+        if !all && version.typ != "release" {
+            continue;
+        }
+
+        let installed = if Path::new(&version_dir!(version.id)).exists() {
+            " (installed)"
+        } else {
+            ""
+        };
+
+        println!(
+            "{id} {GREY}{typ}{GREEN}{installed}{RESET}",
+            id = version.id,
+            typ = version.typ,
+            GREY = colors::GREY,
+            GREEN = colors::GREEN,
+            RESET = colors::RESET
+        );
+    }
+
+    println!(
+        "{MAGENTA}Latest release: {release}, latest snapshot: {snapshot}{RESET}",
+        release = manifest.latest.release,
+        snapshot = manifest.latest.snapshot,
+        MAGENTA = colors::MAGENTA,
+        RESET = colors::RESET
+    );
+
+    Ok(())
 }
 
-pub async fn download(version: String) -> anyhow::Result<()> {
-    println!("Downloading version {version} ...");
+/// Check the SHA-1 of every file of an installed version and download missing or corrupt ones again.
+pub async fn verify(version: String, jobs: usize) -> anyhow::Result<()> {
+    println!("Verifying version {version} ...");
 
-    // get the version manifest from mojang servers
-    let res = send_http!(Method::GET, MAINLINE_VERSIONS).await?;
+    // if the version directory doesn't exist, there is nothing to verify
+    if !Path::new(&version_dir!(version)).exists() {
+        return Err(anyhow!("Version {version} is not installed!"));
+    }
 
-    // parse the version manifest
-    let manifest = res.json::<VersionManifest>().await?;
+    let summary = install(version.clone(), jobs).await?;
 
-    // find the version we want in the manifest
-    let version_manifest = manifest.versions.iter().find(|x| x.id == version);
-
-    // if the version is not found, return an error
-    if version_manifest.is_none() {
-        let available_versions = manifest
-            .versions
-            .iter()
-            .filter(|x| x.typ == "release")
-            .map(|x| x.id.clone())
-            .collect::<Vec<String>>()
-            .join("\n");
-
-        return Err(anyhow!(
-            "No mainline version {} exists.\nAvailable versions:\n{}",
-            version,
-            available_versions
-        ));
+    if summary.downloaded == 0 {
+        println!(
+            "{GREEN}All {checked} files of version {version} are valid{RESET}",
+            checked = summary.checked,
+            GREEN = colors::GREEN,
+            RESET = colors::RESET
+        );
+    } else {
+        println!(
+            "{GREEN}Checked {checked} files of version {version}, repaired {downloaded}{RESET}",
+            checked = summary.checked,
+            downloaded = summary.downloaded,
+            GREEN = colors::GREEN,
+            RESET = colors::RESET
+        );
     }
 
+    Ok(())
+}
+
+/// Get the version details of the given version from the version manifest, as sent by the server and parsed.
+pub async fn version_details(version: &str) -> anyhow::Result<(Vec<u8>, VersionDetails)> {
+    // get the version manifest from mojang servers
+    let manifest = cache::get_json::<VersionManifest>(MAINLINE_VERSIONS, MANIFEST_CACHE).await?;
+
+    // find the version we want in the manifest
+    let version_manifest = match manifest.versions.iter().find(|x| x.id == version) {
+        Some(version_manifest) => version_manifest,
+        // if the version is not found, return an error
+        None => {
+            let available_versions = manifest
+                .versions
+                .iter()
+                .filter(|x| x.typ == "release")
+                .map(|x| x.id.clone())
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            return Err(anyhow!(
+                "No mainline version {} exists.\nAvailable versions:\n{}",
+                version,
+                available_versions
+            ));
+        }
+    };
+
     // get the version details from mojang servers
-    let res = send_http!(Method::GET, version_manifest.unwrap().url.clone()).await?;
+    let raw_details =
+        cache::get(&version_manifest.url, &format!("versions/{version}.json")).await?;
 
     // parse the version details
-    let version_details = res.json::<VersionDetails>().await?;
+    let version_details = serde_json::from_slice::<VersionDetails>(&raw_details)?;
+
+    Ok((raw_details, version_details))
+}
+
+/// Download every file of the given version, skipping the ones that already exist with a valid SHA-1.
+async fn install(version: String, jobs: usize) -> anyhow::Result<Summary> {
+    let (raw_details, version_details) = version_details(&version).await?;
 
     let version_dir = version_dir!(version);
-    let libraries_dir = libraries_dir!(version);
+    let libraries_dir = libraries_dir!();
     let libraries_natives_dir = libraries_natives_dir!(version);
-    let assets_dir = assets_dir!();
     let assets_indexes_dir = assets_indexes_dir!();
     let assets_objects_dir = assets_objects_dir!();
 
     // create the version directory
     fs::create_dir_all(&version_dir)?;
 
-    // download minecraft client jar
-    download_file!(
-        version_details.downloads.client.url,
-        version_details.downloads.client.size,
-        "{version_dir}/{version}.jar",
-    );
-
-    // download assets index
-    download_file!(
-        version_details.asset_index.url,
-        version_details.asset_index.size,
-        "{assets_indexes_dir}/{id}.json",
-        id = version_details.asset_index.id
-    );
-
-    // download logging client file
-    download_file!(
-        version_details.logging.client.file.url,
-        version_details.logging.client.file.size,
-        "{version_dir}/logging-{id}",
-        id = version_details.logging.client.file.id
-    );
+    // move libraries of older installs to the shared store
+    libraries::migrate()?;
+
+    // save the version details as they are, like the vanilla launcher does
+    let tmp = format!("{version_dir}/{version}.json.part");
+    fs::write(&tmp, &raw_details)?;
+    fs::rename(&tmp, format!("{version_dir}/{version}.json"))?;
+
+    // download assets index first, the assets objects are read from it
+    let mut summary = downloader::run(
+        vec![Task::save(
+            version_details.asset_index.url.clone(),
+            version_details.asset_index.size.into(),
+            version_details.asset_index.sha1.clone(),
+            format!(
+                "{assets_indexes_dir}/{id}.json",
+                id = version_details.asset_index.id
+            ),
+        )],
+        jobs,
+    )
+    .await?;
+
+    let mut tasks = vec![
+        // minecraft client jar
+        Task::save(
+            version_details.downloads.client.url.clone(),
+            version_details.downloads.client.size.into(),
+            version_details.downloads.client.sha1.clone(),
+            format!("{version_dir}/{version}.jar"),
+        ),
+    ];
+
+    // logging client file
+    if let Some(logging) = &version_details.logging {
+        tasks.push(Task::save(
+            logging.client.file.url.clone(),
+            logging.client.file.size.into(),
+            logging.client.file.sha1.clone(),
+            format!("{version_dir}/logging-{id}", id = logging.client.file.id),
+        ));
+    }
 
-    let mut classpath = vec![];
-
-    // download libraries
-    for lib in version_details.libraries {
-        // if the library has rules, check if the rules apply to this system
-        if lib.rules.is_some() {
-            let mut allowed = "disallow".to_string();
-
-            for rule in lib.rules.unwrap() {
-                match &rule.os {
-                    Some(os) => {
-                        if os.name == OS_NAME {
-                            allowed = rule.action;
-                            break;
-                        }
-                    }
-                    None => allowed = rule.action,
-                }
-            }
-
-            if allowed != "allow" {
-                continue;
-            }
+    // libraries
+    let env = Environment::current();
+
+    for lib in version_details.libraries(&env) {
+        // the library artifact if it exists
+        if let Some(artifact) = &lib.downloads.artifact {
+            tasks.push(Task::save(
+                artifact.url.clone(),
+                artifact.size.into(),
+                artifact.sha1.clone(),
+                format!("{libraries_dir}/{lib_path}", lib_path = artifact.path),
+            ));
         }
 
-        // download the library artifact if it exists
-        if lib.downloads.artifact.is_some() {
-            let lib = lib.downloads.artifact.unwrap();
-
-            download_file!(
-                lib.url,
-                lib.size,
-                "{libraries_dir}/{lib_path}",
-                lib_path = lib.path
-            );
-
-            classpath.push(format!("libraries/{lib_path}", lib_path = lib.path));
+        // the natives archive for this system if the library has one
+        if let Some(artifact) = lib.natives(&env) {
+            tasks.push(Task::extract(
+                artifact.url.clone(),
+                artifact.size.into(),
+                artifact.sha1.clone(),
+                format!("{libraries_dir}/{lib_path}", lib_path = artifact.path),
+                libraries_natives_dir.clone(),
+                lib.extract
+                    .as_ref()
+                    .map(|x| x.exclude.clone())
+                    .unwrap_or_default(),
+            ));
         }
+    }
 
-        // download the library classifiers (natives) if they exist
-        if lib.downloads.classifiers.is_some() {
-            let lib_natives = lib.natives.unwrap();
-            let natives = lib_natives.get(OS_NAME);
-
-            if natives.is_none() {
-                continue;
-            }
+    // parse the assets index
+    let assets_index = AssetsIndex::load(&version_details.asset_index.id)?;
 
-            let classifier = lib.downloads.classifiers.unwrap();
-            let artifact = classifier.get(natives.unwrap());
+    // move objects of older installs to the hash based layout
+    assets::migrate_objects()?;
 
-            if artifact.is_some() {
-                let artifact = artifact.unwrap();
+    // assets objects, stored by hash so objects shared by multiple names are downloaded once
+    let mut hashes = HashSet::new();
 
-                download_and_extract!(&artifact.url, artifact.size, "{libraries_natives_dir}");
-            }
+    for object in assets_index.objects.values() {
+        if !hashes.insert(&object.hash) {
+            continue;
         }
-    }
-
-    // open the assets index file
-    let file = File::open(format!(
-        "{assets_indexes_dir}/{id}.json",
-        id = version_details.asset_index.id
-    ))?;
 
-    // parse the assets index
-    let assets_index: AssetsIndex = serde_json::from_reader(file)?;
-
-    // download assets objects
-    for (path, object) in assets_index.objects {
-        // get the first two characters of the hash
-        let id = object.hash.chars().take(2).collect::<String>();
+        let object_path = assets::object_path(&object.hash);
 
-        download_file!(
-            format!("{OBJ_SERVER}/{id}/{hash}", hash = object.hash),
+        tasks.push(Task::save(
+            format!("{OBJ_SERVER}/{object_path}"),
             object.size,
-            "{assets_objects_dir}/{path}"
-        );
+            object.hash.clone(),
+            format!("{assets_objects_dir}/{object_path}"),
+        ));
     }
 
-    let game_args = if version_details.minecraft_arguments.is_some() {
-        version_details.minecraft_arguments.unwrap()
-    } else {
-        let mut args = vec![];
+    // extract the natives into an empty directory, so natives of an older version JSON or another system don't stay around
+    if Path::new(&libraries_natives_dir).exists() {
+        fs::remove_dir_all(&libraries_natives_dir)?;
+    }
 
-        for arg in version_details.arguments.unwrap().game {
-            if let VersionDetailsArgumentsGame::String(x) = arg {
-                args.push(x)
-            }
-        }
+    // the Java runtime the version runs on, if Mojang has one for this system, is downloaded with the game files
+    let component = version_details.java_version().component;
+
+    // the game is installed without it, a Java found on the system can still run it
+    let runtime = match Runtime::load(&component).await {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            println!(
+                "{RED}Skipping the Java runtime {component}: {e}{RESET}",
+                RED = colors::RED,
+                RESET = colors::RESET
+            );
 
-        args.join(" ")
+            None
+        }
     };
 
-    classpath.push(format!("{version}.jar"));
-
-    let config = Config {
-        version: version.clone(),
-        assets_root: assets_dir,
-        auth_uuid: 0,
-        auth_access_token: 0,
-        clientid: 0,
-        auth_xuid: 0,
-        version_type: version_manifest.unwrap().typ.clone(),
-        user_type: "legacy".to_string(),
-        launcher_name: "minecraft-launcher".to_string(),
-        launcher_version: "2.1.1349".to_string(),
-        main: version_details.main_class,
-        assets_index_name: version_details.asset_index.id.clone(),
-        natives_directory: "libraries-natives".to_string(),
-        log_path: format!("logging-{id}", id = version_details.logging.client.file.id),
-        classpath,
-        java: "".to_string(),
-        jvm_opts: "-Xss1M -Djava.library.path=${natives_directory} -Dminecraft.launcher.brand=${launcher_name} -Dminecraft.launcher.version=${launcher_version} -Dlog4j.configurationFile=${log_path} -cp ${classpath}".to_string(),
-        game_args,
-    };
+    if let Some(runtime) = &runtime {
+        tasks.extend(runtime.tasks()?);
+    }
 
-    let config_str = toml::to_string(&config)?;
+    summary.merge(downloader::run(tasks, jobs).await?);
 
-    let mut config_file = File::create(format!("{version_dir}/config.toml"))?;
-    config_file.write_all(config_str.as_bytes())?;
+    if let Some(runtime) = &runtime {
+        runtime.finish()?;
+    }
 
-    Ok(())
+    // legacy versions read the assets by name from the virtual directory
+    if assets_index.is_virtual {
+        assets_index.materialize(&assets_virtual_dir!(version_details.asset_index.id))?;
+    }
+
+    Ok(summary)
 }
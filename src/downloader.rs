@@ -0,0 +1,365 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use reqwest::header::CONTENT_RANGE;
+use reqwest::{Method, StatusCode};
+use sha1::{Digest, Sha1};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::http_client::{self, build_request, send_http};
+use crate::progress::{self, Progress};
+use crate::{colors, hash, settings};
+
+/// Default number of files downloaded at the same time.
+pub const DEFAULT_JOBS: usize = 8;
+
+/// How many times a file is downloaded from a source before a checksum mismatch makes it fall back to the next one.
+const CHECKSUM_ATTEMPTS: usize = 3;
+
+/// What to do with a downloaded file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Save the file to the task path.
+    Save,
+    /// Save the zip archive to the task path and extract it into the given directory,
+    /// skipping the entries starting with one of the excluded prefixes.
+    Extract { dir: String, exclude: Vec<String> },
+}
+
+/// A single file to download.
+#[derive(Debug, Clone)]
+pub struct Task {
+    pub url: String,
+    pub path: String,
+    pub size: u64,
+    pub sha1: String,
+    pub action: Action,
+}
+
+impl Task {
+    /// Download a file and save it to the given path.
+    pub fn save(url: String, size: u64, sha1: String, path: String) -> Self {
+        Self {
+            url,
+            path,
+            size,
+            sha1,
+            action: Action::Save,
+        }
+    }
+
+    /// Download a zip archive to the given path and extract it into the given directory, without the excluded entries.
+    pub fn extract(
+        url: String,
+        size: u64,
+        sha1: String,
+        path: String,
+        dir: String,
+        exclude: Vec<String>,
+    ) -> Self {
+        Self {
+            url,
+            path,
+            size,
+            sha1,
+            action: Action::Extract { dir, exclude },
+        }
+    }
+}
+
+/// Number of files checked and (re)downloaded by the scheduler.
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub checked: usize,
+    pub downloaded: usize,
+}
+
+impl Summary {
+    /// Add the counts of another summary to this one.
+    pub fn merge(&mut self, other: Summary) {
+        self.checked += other.checked;
+        self.downloaded += other.downloaded;
+    }
+}
+
+/// Run the given tasks with at most `jobs` transfers at the same time.
+///
+/// A failed task doesn't stop the other ones, all failures are reported together once every task has finished.
+pub async fn run(tasks: Vec<Task>, jobs: usize) -> anyhow::Result<Summary> {
+    let total = tasks.len();
+    let progress = Progress::start(total, tasks.iter().map(|x| x.size).sum());
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut set = JoinSet::new();
+
+    for task in tasks {
+        let semaphore = semaphore.clone();
+        let progress = progress.clone();
+
+        set.spawn(async move {
+            // wait for a free slot, the semaphore is never closed
+            let _permit = semaphore.acquire_owned().await.unwrap();
+
+            let result = match &task.action {
+                Action::Save => save(&task, &progress).await,
+                Action::Extract { dir, exclude } => extract(&task, dir, exclude, &progress).await,
+            };
+
+            progress.file_done();
+
+            (task, result)
+        });
+    }
+
+    let mut summary = Summary::default();
+    let mut failures = vec![];
+
+    while let Some(res) = set.join_next().await {
+        let (task, result) = res?;
+
+        match result {
+            Ok(downloaded) => {
+                summary.checked += 1;
+
+                if downloaded {
+                    summary.downloaded += 1;
+                }
+            }
+            Err(e) => failures.push(format!("{path}: {e}", path = task.path)),
+        }
+    }
+
+    progress.finish();
+
+    if !failures.is_empty() {
+        return Err(anyhow!(
+            "Failed to download {failed} of {total} files:\n{failures}",
+            failed = failures.len(),
+            failures = failures.join("\n")
+        ));
+    }
+
+    Ok(summary)
+}
+
+/// Download a file to the task path and verify its SHA-1 hash.
+///
+/// Files that already exist are only downloaded again if their hash doesn't match.
+/// Returns `true` if the file was (re)downloaded.
+async fn save(task: &Task, progress: &Progress) -> anyhow::Result<bool> {
+    let path = &task.path;
+    let exists = Path::new(path).exists();
+
+    if exists && hash::verify_file(path, &task.sha1) {
+        progress.skipped(task.size);
+
+        return Ok(false);
+    }
+
+    if settings::get().offline {
+        return Err(anyhow!(
+            "The file is missing or corrupt and can't be downloaded in offline mode"
+        ));
+    }
+
+    if exists {
+        progress::println(&format!(
+            "{RED}{path} is corrupt, downloading it again{RESET}",
+            RED = colors::RED,
+            RESET = colors::RESET
+        ));
+    }
+
+    if !progress.is_live() {
+        progress::println(&format!(
+            "{MAGENTA}Downloading {GREY}{path} ... {MAGENTA}({size}){RESET}",
+            size = progress::format_bytes(task.size),
+            MAGENTA = colors::MAGENTA,
+            GREY = colors::GREY,
+            RESET = colors::RESET
+        ));
+    }
+
+    // create the directory if it doesn't exist
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tmp = part_path(path);
+    let counted = AtomicU64::new(0);
+
+    // a mirror with a stale or truncated copy counts as failed, so the next source is tried
+    http_client::request(&task.url, |url| {
+        let (tmp, counted) = (&tmp, &counted);
+
+        async move {
+            for attempt in 1..=CHECKSUM_ATTEMPTS {
+                let (hash, size) = fetch(&url, tmp, task.size, progress, counted).await?;
+
+                if hash.eq_ignore_ascii_case(&task.sha1) && size == task.size {
+                    return Ok(());
+                }
+
+                fs::remove_file(tmp)?;
+                progress.discarded(counted.swap(0, Ordering::Relaxed));
+
+                if attempt == CHECKSUM_ATTEMPTS {
+                    return Err(anyhow!(
+                        "Checksum mismatch: expected {expected} ({expected_size} bytes), got {hash} ({size} bytes)",
+                        expected = task.sha1,
+                        expected_size = task.size
+                    ));
+                }
+
+                progress::println(&format!(
+                    "{RED}Checksum mismatch for {path} from {url}, retrying ...{RESET}",
+                    RED = colors::RED,
+                    RESET = colors::RESET
+                ));
+            }
+
+            unreachable!()
+        }
+    })
+    .await?;
+
+    // the file is complete, move it into place
+    fs::rename(&tmp, path)?;
+
+    Ok(true)
+}
+
+/// Download a zip archive like [`save`] and extract it into the given directory.
+///
+/// The archive is extracted every time, even if it didn't have to be downloaded.
+/// Entries starting with one of the excluded prefixes (like `META-INF/`) are skipped.
+async fn extract(
+    task: &Task,
+    dir: &str,
+    exclude: &[String],
+    progress: &Progress,
+) -> anyhow::Result<bool> {
+    let downloaded = save(task, progress).await?;
+
+    // create the directory if it doesn't exist
+    fs::create_dir_all(dir)?;
+
+    // extract the zip archive
+    let mut archive = zip::ZipArchive::new(File::open(&task.path)?)?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+
+        if exclude.iter().any(|x| entry.name().starts_with(x.as_str())) {
+            continue;
+        }
+
+        // skip entries that would be written outside of the directory
+        let path = match entry.enclosed_name() {
+            Some(name) => Path::new(dir).join(name),
+            None => continue,
+        };
+
+        if entry.is_dir() {
+            fs::create_dir_all(&path)?;
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        io::copy(&mut entry, &mut File::create(&path)?)?;
+    }
+
+    Ok(downloaded)
+}
+
+/// Path of the temporary file a download is written to before it's moved into place.
+fn part_path(path: &str) -> PathBuf {
+    PathBuf::from(format!("{path}.part"))
+}
+
+/// Stream the response body of the given URL into a file, chunk by chunk.
+///
+/// If a partial file from an interrupted download exists, only the missing part is requested with a `Range` header.
+/// When the server ignores the range, the file is downloaded again from the start.
+/// The file is flushed to disk before returning its SHA-1 hash and size.
+///
+/// `counted` holds the bytes of this file already added to the progress, it always matches the size of the file.
+async fn fetch(
+    url: &str,
+    path: &Path,
+    expected_size: u64,
+    progress: &Progress,
+    counted: &AtomicU64,
+) -> anyhow::Result<(String, u64)> {
+    // continue from a partial file left behind by an interrupted download
+    let offset = match fs::metadata(path) {
+        Ok(metadata) if metadata.len() < expected_size => metadata.len(),
+        _ => 0,
+    };
+
+    let res = if offset > 0 {
+        let req = build_request!(Method::GET, url, "Range" => format!("bytes={offset}-"))?;
+        http_client::timeout(send_http!(req)).await?
+    } else {
+        http_client::timeout(send_http!(Method::GET, url)).await?
+    };
+    let mut res = res.error_for_status()?;
+
+    // check if the server sent the range we asked for
+    let resumed = offset > 0
+        && res.status() == StatusCode::PARTIAL_CONTENT
+        && res
+            .headers()
+            .get(CONTENT_RANGE)
+            .and_then(|x| x.to_str().ok())
+            .is_some_and(|x| x.starts_with(&format!("bytes {offset}-")));
+
+    let mut hasher = Sha1::new();
+    let mut size = 0;
+
+    let mut file = if resumed {
+        if !progress.is_live() {
+            progress::println(&format!(
+                "{MAGENTA}Resuming download of {GREY}{url} {MAGENTA}from byte {offset}{RESET}",
+                MAGENTA = colors::MAGENTA,
+                GREY = colors::GREY,
+                RESET = colors::RESET
+            ));
+        }
+
+        // hash the data we already have and append the rest to it
+        let mut file = OpenOptions::new().read(true).append(true).open(path)?;
+        size = io::copy(&mut file, &mut hasher)?;
+
+        // bytes left behind by an earlier run weren't counted yet
+        progress.skipped(size.saturating_sub(counted.swap(size, Ordering::Relaxed)));
+
+        file
+    } else {
+        progress.discarded(counted.swap(0, Ordering::Relaxed));
+
+        File::create(path)?
+    };
+
+    while let Some(chunk) = http_client::timeout(res.chunk()).await? {
+        file.write_all(&chunk)?;
+        hasher.update(&chunk);
+        size += chunk.len() as u64;
+
+        counted.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        progress.transferred(chunk.len() as u64);
+    }
+
+    file.sync_all()?;
+
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
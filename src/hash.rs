@@ -0,0 +1,28 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use sha1::{Digest, Sha1};
+
+/// Calculate the SHA-1 hash of the file at the given path as a lowercase hex string.
+pub fn sha1_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha1::new();
+
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Calculate the SHA-1 hash of the given data as a lowercase hex string.
+pub fn sha1_bytes(data: &[u8]) -> String {
+    format!("{:x}", Sha1::digest(data))
+}
+
+/// Check if the file at the given path exists and its SHA-1 hash matches the expected one.
+pub fn verify_file<P: AsRef<Path>>(path: P, sha1: &str) -> bool {
+    match sha1_file(path) {
+        Ok(hash) => hash.eq_ignore_ascii_case(sha1),
+        Err(_) => false,
+    }
+}
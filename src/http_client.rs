@@ -1,18 +1,50 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::anyhow;
 use once_cell::sync::Lazy;
-use reqwest::Client;
+use reqwest::{Client, Proxy, StatusCode};
+use tokio::time::error::Elapsed;
 
-pub static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+use crate::settings::HttpSettings;
+use crate::{progress, settings};
 
-/// Build a request to the given URL.
-macro_rules! build_request {
-    ($method: expr, $uri: expr) => {
+pub static CLIENT: Lazy<Client> = Lazy::new(|| {
+    let settings = settings::get();
+
+    let mut builder =
+        Client::builder().connect_timeout(Duration::from_secs(settings.http.connect_timeout));
+
+    // the proxy URL is validated when the settings are loaded
+    if let Some(proxy_url) = settings.proxy.proxy_url().unwrap() {
+        builder = builder.proxy(Proxy::custom(move |url| match url.host_str() {
+            Some(host) if settings.proxy.bypass(host) => None,
+            _ => Some(proxy_url.clone()),
+        }));
+    }
+
+    builder.build().expect("failed to build the http client")
+});
+
+/// Start building a request to the given URL, optionally with extra headers.
+macro_rules! request_builder {
+    ($method: expr, $uri: expr $(, $name: expr => $value: expr)* $(,)?) => {
         crate::http_client::CLIENT
             .request($method, $uri)
             .header(
                 "User-Agent",
                 format!("Watercraft/{}", env!("CARGO_PKG_VERSION")),
             )
-            .build()
+            $(.header($name, $value))*
+    };
+}
+
+/// Build a request to the given URL, optionally with extra headers.
+macro_rules! build_request {
+    ($($args: tt)*) => {
+        crate::http_client::request_builder!($($args)*).build()
     };
 }
 
@@ -26,83 +58,306 @@ macro_rules! send_http {
     };
 }
 
-/// Download a file from the given URL.
-macro_rules! download {
-    ($uri: expr) => {
-        // get the file content from the URL
-        std::io::Cursor::new(send_http!(Method::GET, $uri).await?.bytes().await?)
-    };
-    ($writer: expr, $uri: expr) => {
-        // get the file content from the URL
-        let mut res = crate::http_client::download!($uri);
+/// Await a response or a chunk of its body, failing if it doesn't arrive within the configured read timeout.
+pub async fn timeout<T>(fut: impl Future<Output = reqwest::Result<T>>) -> anyhow::Result<T> {
+    let read_timeout = Duration::from_secs(settings::get().http.read_timeout);
 
-        // write content to writer
-        std::io::copy(&mut res, $writer)?;
-    };
+    Ok(tokio::time::timeout(read_timeout, fut).await??)
 }
 
-macro_rules! download_file {
-    ($uri: expr, $size: expr, $($path:tt)*) => {
-        let path = format!($($path)*);
+/// Get the URLs the given URL can be downloaded from, with the mirrors keyed by URL prefix.
+///
+/// The mirrors of the longest matching prefix come first, in their configured order, followed by the
+/// mirrors of shorter prefixes and the URL itself.
+pub fn sources(url: &str, mirrors: &HashMap<String, Vec<String>>) -> Vec<String> {
+    let mut prefixes = mirrors
+        .iter()
+        .filter_map(|(prefix, mirrors)| {
+            let prefix = prefix.trim_end_matches('/');
+            let rest = url.strip_prefix(prefix)?;
 
-        if std::path::Path::new(&path).exists() {
-            println!(
-                "{GREY}{path} {GREEN}already exists, skipping download{RESET}",
-                GREY = crate::colors::GREY,
-                GREEN = crate::colors::GREEN,
-                RESET = crate::colors::RESET
-            );
-        } else {
-            let unit = byte_unit::Byte::from_bytes($size.into()).get_appropriate_unit(false).to_string();
-            println!(
-                "{MAGENTA}Downloading {GREY}{path} ... {MAGENTA}({unit}){RESET}",
-                MAGENTA = crate::colors::MAGENTA,
-                GREY = crate::colors::GREY,
-                RESET = crate::colors::RESET
-            );
+            // the prefix has to end at a path segment boundary
+            if !rest.is_empty() && !rest.starts_with('/') {
+                return None;
+            }
+
+            Some((prefix.len(), mirrors, rest))
+        })
+        .collect::<Vec<_>>();
+
+    prefixes.sort_by_key(|x| Reverse(x.0));
 
-            // create the directory if it doesn't exist
-            let mut dir = path.split('/').collect::<Vec<&str>>();
-            dir.pop();
-            std::fs::create_dir_all(dir.join("/"))?;
+    let mut sources = prefixes
+        .into_iter()
+        .flat_map(|(_, mirrors, rest)| {
+            mirrors
+                .iter()
+                .map(move |mirror| format!("{}{rest}", mirror.trim_end_matches('/')))
+        })
+        .collect::<Vec<_>>();
 
-            // create the file and open it for writing
-            let mut file = fs::File::create(format!("{}", path))?;
+    sources.push(url.to_string());
 
-            crate::http_client::download!(&mut file, $uri);
+    sources
+}
+
+/// Run the given request against every source of the URL until one of them succeeds.
+///
+/// Every source is retried on its own, see [`retry`], before falling back to the next one.
+pub async fn request<T, F, Fut>(url: &str, mut request: F) -> anyhow::Result<T>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    if settings::get().offline {
+        return Err(anyhow!("Can't download {url} in offline mode"));
+    }
+
+    let sources = sources(url, &settings::get().mirrors);
+    let mut errors = vec![];
+
+    for (i, source) in sources.iter().enumerate() {
+        let err = match retry(source, || request(source.clone())).await {
+            Ok(res) => return Ok(res),
+            Err(e) => e,
+        };
+
+        if let Some(next) = sources.get(i + 1) {
+            progress::println(&format!(
+                "{RED}{err}, falling back to {next}{RESET}",
+                RED = crate::colors::RED,
+                RESET = crate::colors::RESET
+            ));
         }
-    };
+
+        errors.push(err.to_string());
+    }
+
+    if errors.len() == 1 {
+        return Err(anyhow!(errors.remove(0)));
+    }
+
+    Err(anyhow!(
+        "All sources of {url} failed:\n{errors}",
+        errors = errors.join("\n")
+    ))
 }
 
-macro_rules! download_and_extract {
-    ($uri: expr, $size: expr, $($path:tt)*) => {
-        let path = format!($($path)*);
+/// Run the given request until it succeeds or the configured retries run out.
+///
+/// Transient failures (timeouts, connection errors, 408, 429 and 5xx responses) are retried with exponential
+/// backoff and jitter, any other error is returned immediately.
+async fn retry<T, F, Fut>(url: &str, mut request: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let http = &settings::get().http;
+    let attempts = http.retries + 1;
+
+    for attempt in 1.. {
+        let err = match request().await {
+            Ok(res) => return Ok(res),
+            Err(e) => e,
+        };
+
+        if attempt >= attempts || !is_transient(&err) {
+            let plural = if attempt == 1 { "" } else { "s" };
+
+            return Err(anyhow!(
+                "GET {url} failed after {attempt} attempt{plural}: {err}"
+            ));
+        }
+
+        let delay = retry_delay(http, attempt);
 
-        let unit = byte_unit::Byte::from_bytes($size.into()).get_appropriate_unit(false).to_string();
-        println!(
-            "{MAGENTA}Downloading and Extracting {GREY}{url} ... {MAGENTA}({unit}){RESET}",
-            url = $uri,
-            MAGENTA = crate::colors::MAGENTA,
-            GREY = crate::colors::GREY,
+        progress::println(&format!(
+            "{RED}GET {url} failed ({err}), retrying in {delay} ms ...{RESET}",
+            RED = crate::colors::RED,
             RESET = crate::colors::RESET
-        );
+        ));
 
-        // create the directory if it doesn't exist
-        let mut dir = path.split('/').collect::<Vec<&str>>();
-        dir.pop();
-        std::fs::create_dir_all(dir.join("/"))?;
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
 
-        let res = crate::http_client::download!($uri);
+    unreachable!()
+}
 
-        // extract the zip archive
-        let mut archive = zip::ZipArchive::new(res)?;
+/// Get the milliseconds to wait after the given failed attempt.
+///
+/// The delay doubles on every attempt up to the maximum, a random delay between half and the full value is used.
+fn retry_delay(http: &HttpSettings, attempt: u32) -> u64 {
+    let delay = http
+        .retry_delay
+        .saturating_mul(1 << (attempt - 1).min(16))
+        .min(http.max_retry_delay);
 
-        archive.extract(path)?;
-    };
+    fastrand::u64(delay / 2..=delay)
+}
+
+/// Check if the request that failed with the given error can succeed when retried.
+fn is_transient(err: &anyhow::Error) -> bool {
+    if err.is::<Elapsed>() {
+        return true;
+    }
+
+    match err.downcast_ref::<reqwest::Error>() {
+        Some(e) => match e.status() {
+            Some(status) => {
+                status.is_server_error()
+                    || status == StatusCode::REQUEST_TIMEOUT
+                    || status == StatusCode::TOO_MANY_REQUESTS
+            }
+            // the request didn't get a response (connection reset, timeout, broken body)
+            None => !e.is_builder() && !e.is_decode(),
+        },
+        None => false,
+    }
 }
 
 pub(crate) use build_request;
-pub(crate) use download;
-pub(crate) use download_and_extract;
-pub(crate) use download_file;
+pub(crate) use request_builder;
 pub(crate) use send_http;
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    fn status_error(status: u16) -> anyhow::Error {
+        let res = http::Response::builder().status(status).body("").unwrap();
+
+        reqwest::Response::from(res)
+            .error_for_status()
+            .unwrap_err()
+            .into()
+    }
+
+    #[test]
+    fn transient_statuses() {
+        for status in [500, 502, 503, 504, 408, 429] {
+            assert!(is_transient(&status_error(status)), "{status}");
+        }
+
+        for status in [400, 401, 403, 404, 410] {
+            assert!(!is_transient(&status_error(status)), "{status}");
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_errors() {
+        let elapsed = tokio::time::timeout(Duration::ZERO, std::future::pending::<()>())
+            .await
+            .unwrap_err();
+        assert!(is_transient(&elapsed.into()));
+
+        // nothing listens on port 1
+        let refused = CLIENT.get("http://127.0.0.1:1").send().await.unwrap_err();
+        assert!(is_transient(&refused.into()));
+
+        let invalid = CLIENT.get("not a url").build().unwrap_err();
+        assert!(!is_transient(&invalid.into()));
+    }
+
+    #[test]
+    fn other_errors_are_not_transient() {
+        assert!(!is_transient(&anyhow!("Checksum mismatch")));
+        assert!(!is_transient(
+            &io::Error::from(io::ErrorKind::PermissionDenied).into()
+        ));
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_maximum() {
+        let http = HttpSettings {
+            retry_delay: 500,
+            max_retry_delay: 30_000,
+            ..Default::default()
+        };
+
+        for (attempt, full) in [(1, 500), (2, 1000), (3, 2000), (7, 30_000), (100, 30_000)] {
+            for _ in 0..100 {
+                let delay = retry_delay(&http, attempt);
+                assert!(delay >= full / 2 && delay <= full, "{attempt}: {delay}");
+            }
+        }
+    }
+
+    fn mirrors(entries: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(prefix, mirrors)| {
+                (
+                    prefix.to_string(),
+                    mirrors.iter().map(|x| x.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn url_without_mirrors() {
+        assert_eq!(
+            sources("https://libraries.minecraft.net/a.jar", &HashMap::new()),
+            ["https://libraries.minecraft.net/a.jar"]
+        );
+    }
+
+    #[test]
+    fn mirrors_come_before_the_url() {
+        let mirrors = mirrors(&[(
+            "https://libraries.minecraft.net",
+            &["https://a.example/libs/", "https://b.example/libs"],
+        )]);
+
+        assert_eq!(
+            sources(
+                "https://libraries.minecraft.net/org/lwjgl/lwjgl.jar",
+                &mirrors
+            ),
+            [
+                "https://a.example/libs/org/lwjgl/lwjgl.jar",
+                "https://b.example/libs/org/lwjgl/lwjgl.jar",
+                "https://libraries.minecraft.net/org/lwjgl/lwjgl.jar",
+            ]
+        );
+    }
+
+    #[test]
+    fn longest_prefix_first() {
+        let mirrors = mirrors(&[
+            ("https://piston-data.mojang.com", &["https://all.example"]),
+            (
+                "https://piston-data.mojang.com/v1/objects/",
+                &["https://objects.example"],
+            ),
+        ]);
+
+        assert_eq!(
+            sources(
+                "https://piston-data.mojang.com/v1/objects/abc/client.jar",
+                &mirrors
+            ),
+            [
+                "https://objects.example/abc/client.jar",
+                "https://all.example/v1/objects/abc/client.jar",
+                "https://piston-data.mojang.com/v1/objects/abc/client.jar",
+            ]
+        );
+    }
+
+    #[test]
+    fn prefix_ends_at_a_segment_boundary() {
+        let mirrors = mirrors(&[("https://example.com/v1", &["https://mirror.example"])]);
+
+        assert_eq!(
+            sources("https://example.com/v10/file", &mirrors),
+            ["https://example.com/v10/file"]
+        );
+        assert_eq!(
+            sources("https://example.com/v1", &mirrors),
+            ["https://mirror.example", "https://example.com/v1"]
+        );
+    }
+}
@@ -0,0 +1,298 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::anyhow;
+
+use crate::config::{game_dir, HOME};
+use crate::{colors, downloader, runtime};
+
+/// A Java installation found on the system.
+#[derive(Debug, Clone)]
+pub struct Installation {
+    /// Path of the `java` executable.
+    pub path: PathBuf,
+    /// Full version, like `17.0.8` or `1.8.0_382`.
+    pub version: String,
+    pub major_version: u32,
+    /// CPU architecture in the same form as the rules use, like `x86_64` or `aarch64`.
+    pub arch: String,
+    pub vendor: String,
+    /// Installed by watercraft in the `runtimes/` directory.
+    pub managed: bool,
+}
+
+/// Find the Java installations in the managed runtimes, `JAVA_HOME`, `PATH`, `/usr/lib/jvm` and SDKMAN.
+///
+/// Every candidate is run to get its version and architecture, the ones that can't be run are skipped.
+pub fn detect() -> Vec<Installation> {
+    let mut candidates = vec![];
+
+    // managed runtimes first, they're the ones the versions ask for
+    candidates.extend(
+        subdirectories(&game_dir!("runtimes"))
+            .into_iter()
+            .map(|dir| (dir.join("bin/java"), true)),
+    );
+
+    if let Some(java_home) = env::var_os("JAVA_HOME") {
+        candidates.push((PathBuf::from(java_home).join("bin/java"), false));
+    }
+
+    if let Some(path) = env::var_os("PATH") {
+        candidates.extend(env::split_paths(&path).map(|dir| (dir.join("java"), false)));
+    }
+
+    candidates.extend(
+        subdirectories("/usr/lib/jvm")
+            .into_iter()
+            .map(|dir| (dir.join("bin/java"), false)),
+    );
+
+    let sdkman_dir =
+        env::var("SDKMAN_DIR").unwrap_or_else(|_| format!("{home}/.sdkman", home = *HOME));
+    candidates.extend(
+        subdirectories(&format!("{sdkman_dir}/candidates/java"))
+            .into_iter()
+            .map(|dir| (dir.join("bin/java"), false)),
+    );
+
+    let mut seen = HashSet::new();
+    let mut installations = vec![];
+
+    for (path, managed) in candidates {
+        // the same installation is often reachable through several symlinks
+        let real_path = match fs::canonicalize(&path) {
+            Ok(real_path) => real_path,
+            Err(_) => continue,
+        };
+
+        if !seen.insert(real_path) {
+            continue;
+        }
+
+        if let Some(installation) = probe(&path, managed) {
+            installations.push(installation);
+        }
+    }
+
+    installations
+}
+
+/// Print the Java installations found on the system.
+pub fn list() -> anyhow::Result<()> {
+    let installations = detect();
+
+    if installations.is_empty() {
+        return Err(anyhow!("No Java installation found"));
+    }
+
+    for installation in installations {
+        let managed = if installation.managed {
+            " (managed)"
+        } else {
+            ""
+        };
+
+        println!(
+            "Java {major} {GREY}{version} {vendor} {arch}{GREEN}{managed}{RESET} {path}",
+            major = installation.major_version,
+            version = installation.version,
+            vendor = installation.vendor,
+            arch = installation.arch,
+            path = installation.path.display(),
+            GREY = colors::GREY,
+            GREEN = colors::GREEN,
+            RESET = colors::RESET
+        );
+    }
+
+    Ok(())
+}
+
+/// Choose the Java installation to run a version with.
+///
+/// The managed runtime of the version is used if it's installed, then a system installation of the required
+/// major version. If there is none, the managed runtime is downloaded, or the closest newer Java is used when
+/// Mojang has no runtime of the version for this system or the runtime manifest can't be fetched.
+pub async fn select(component: &str, major_version: u32) -> anyhow::Result<String> {
+    if runtime::is_installed(component) {
+        return Ok(runtime::java_path(component));
+    }
+
+    let arch = env::consts::ARCH;
+    let mut installations = detect()
+        .into_iter()
+        .filter(|x| x.arch == arch && x.major_version >= major_version)
+        .collect::<Vec<Installation>>();
+
+    installations.sort_by_key(|x| x.major_version);
+
+    let installation = installations.first();
+
+    if let Some(installation) = installation {
+        if installation.major_version == major_version {
+            return Ok(installation.path.to_string_lossy().to_string());
+        }
+    }
+
+    // without the runtime manifest a newer Java is still better than not starting at all
+    let available = match runtime::is_available(component).await {
+        Ok(available) => available,
+        Err(_) if installation.is_some() => false,
+        Err(e) => return Err(e),
+    };
+
+    if available {
+        runtime::install(component, downloader::DEFAULT_JOBS).await?;
+
+        return Ok(runtime::java_path(component));
+    }
+
+    if let Some(installation) = installation {
+        println!(
+            "{RED}Java {major_version} not found, using Java {found} instead{RESET}",
+            found = installation.major_version,
+            RED = colors::RED,
+            RESET = colors::RESET
+        );
+
+        return Ok(installation.path.to_string_lossy().to_string());
+    }
+
+    Err(anyhow!(
+        "Java {major_version} or newer is needed but wasn't found, install it or choose one with --java"
+    ))
+}
+
+/// Run `java -XshowSettings:properties -version` and read the version and architecture from its output.
+fn probe(path: &Path, managed: bool) -> Option<Installation> {
+    let output = Command::new(path)
+        .args(["-XshowSettings:properties", "-version"])
+        .output()
+        .ok()?;
+
+    parse_properties(path, managed, &String::from_utf8_lossy(&output.stderr))
+}
+
+/// Read an installation from the output of `java -XshowSettings:properties -version`.
+fn parse_properties(path: &Path, managed: bool, output: &str) -> Option<Installation> {
+    // the properties are printed to stderr as `    name = value`
+    let properties = output
+        .lines()
+        .filter_map(|line| line.split_once(" = "))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect::<HashMap<String, String>>();
+
+    let version = properties.get("java.version")?.clone();
+
+    Some(Installation {
+        path: path.to_path_buf(),
+        major_version: major_version(&version)?,
+        version,
+        arch: normalize_arch(properties.get("os.arch")?),
+        vendor: properties.get("java.vendor").cloned().unwrap_or_default(),
+        managed,
+    })
+}
+
+/// Get the major version of a Java version, `1.8.0_382` is Java 8 and `17.0.8` is Java 17.
+fn major_version(version: &str) -> Option<u32> {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
+    }
+}
+
+/// Convert the `os.arch` of Java to the name Rust uses.
+fn normalize_arch(arch: &str) -> String {
+    match arch {
+        "amd64" => "x86_64",
+        "i386" | "i486" | "i586" | "i686" => "x86",
+        "arm64" => "aarch64",
+        arch => arch,
+    }
+    .to_string()
+}
+
+/// List the directories in the given directory, an empty list if it can't be read.
+fn subdirectories(dir: &str) -> Vec<PathBuf> {
+    let mut dirs = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect::<Vec<PathBuf>>()
+        })
+        .unwrap_or_default();
+
+    dirs.sort();
+
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn java_17_properties() {
+        let output = include_str!("../tests/fixtures/java-17-properties.txt");
+        let path = Path::new("/usr/lib/jvm/java-17-openjdk-amd64/bin/java");
+
+        let installation = parse_properties(path, false, output).unwrap();
+        assert_eq!(installation.path, path);
+        assert_eq!(installation.version, "17.0.15");
+        assert_eq!(installation.major_version, 17);
+        assert_eq!(installation.arch, "x86_64");
+        assert_eq!(installation.vendor, "Debian");
+        assert!(!installation.managed);
+    }
+
+    #[test]
+    fn java_8_properties() {
+        let output = "Property settings:
+    java.vendor = Temurin
+    java.version = 1.8.0_382
+    os.arch = aarch64
+
+openjdk version \"1.8.0_382\"
+";
+
+        let installation = parse_properties(Path::new("java"), true, output).unwrap();
+        assert_eq!(installation.major_version, 8);
+        assert_eq!(installation.arch, "aarch64");
+        assert!(installation.managed);
+    }
+
+    #[test]
+    fn output_without_properties() {
+        assert!(parse_properties(Path::new("java"), false, "Unrecognized option").is_none());
+        assert!(parse_properties(Path::new("java"), false, "    java.version = 17.0.8").is_none());
+    }
+
+    #[test]
+    fn major_versions() {
+        assert_eq!(major_version("1.8.0_382"), Some(8));
+        assert_eq!(major_version("1.7.0_80"), Some(7));
+        assert_eq!(major_version("17.0.8"), Some(17));
+        assert_eq!(major_version("21"), Some(21));
+        assert_eq!(major_version("17-ea"), Some(17));
+        assert_eq!(major_version("22.0.1+8"), Some(22));
+        assert_eq!(major_version(""), None);
+        assert_eq!(major_version("openjdk"), None);
+    }
+
+    #[test]
+    fn architectures() {
+        assert_eq!(normalize_arch("amd64"), "x86_64");
+        assert_eq!(normalize_arch("i386"), "x86");
+        assert_eq!(normalize_arch("arm64"), "aarch64");
+        assert_eq!(normalize_arch("aarch64"), "aarch64");
+    }
+}
@@ -1,11 +1,39 @@
 use anyhow::anyhow;
-use std::fs;
 use std::path::Path;
 use std::process::Command;
+use std::{env, fs};
 
-use crate::config::{profile_dir, version_dir, Config};
+use crate::arguments::Placeholders;
+use crate::assets::AssetsIndex;
+use crate::config::{libraries_dir, profile_dir, version_dir};
+use crate::rules::Environment;
+use crate::settings::LaunchOptions;
+use crate::version::VersionDetails;
+use crate::{auth, java, libraries, settings};
 
-pub async fn launch(version: String, username: String, java: Option<String>) -> anyhow::Result<()> {
+/// Size of the game window when only one of the dimensions is set, the same as the vanilla launcher.
+const DEFAULT_WIDTH: u32 = 854;
+const DEFAULT_HEIGHT: u32 = 480;
+
+/// Port of a Minecraft server if the address doesn't have one.
+const DEFAULT_PORT: u16 = 25565;
+
+/// Where the game goes right after it has started.
+#[derive(Debug, Clone)]
+pub enum QuickPlay {
+    /// Open the singleplayer world with the given name.
+    World(String),
+    /// Join the server at the given `host[:port]` address.
+    Server(String),
+}
+
+pub async fn launch(
+    version: String,
+    username: String,
+    java: Option<String>,
+    options: LaunchOptions,
+    quick_play: Option<QuickPlay>,
+) -> anyhow::Result<()> {
     println!("Launching version {version} as {username} ...");
 
     let version_dir = version_dir!(version);
@@ -15,51 +43,239 @@ pub async fn launch(version: String, username: String, java: Option<String>) ->
         return Err(anyhow!("Version {version} does not exist!"));
     }
 
-    // parse config file
-    let cfg_file = fs::read_to_string(format!("{version_dir}/config.toml"))?;
-    let config: Config = toml::from_str(&cfg_file)?;
+    // move libraries of older installs to the shared store
+    libraries::migrate()?;
+
+    // the options of the command override the ones of the instance
+    let options = match settings::get().instances.get(&version) {
+        Some(instance) => options.or(instance),
+        None => options,
+    };
+
+    // the launch options enable the rule-gated game arguments
+    let mut env = Environment::current();
+
+    if options.width.is_some() || options.height.is_some() {
+        env.features
+            .insert("has_custom_resolution".to_string(), true);
+    }
+
+    if options.demo == Some(true) {
+        env.features.insert("is_demo_user".to_string(), true);
+    }
+
+    let version_details = VersionDetails::load(&version)?;
+
+    // versions from 23w14a have Quick Play, older ones can only join a server with `--server` and `--port`
+    let mut legacy_server = None;
+
+    match &quick_play {
+        Some(QuickPlay::World(_)) if !version_details.has_feature("is_quick_play_singleplayer") => {
+            return Err(anyhow!(
+                "Version {version} can't open a world on start, it needs Quick Play (23w14a or newer)"
+            ));
+        }
+        Some(QuickPlay::World(_)) => {
+            env.features
+                .insert("is_quick_play_singleplayer".to_string(), true);
+        }
+        Some(QuickPlay::Server(_)) if version_details.has_feature("is_quick_play_multiplayer") => {
+            env.features
+                .insert("is_quick_play_multiplayer".to_string(), true);
+        }
+        Some(QuickPlay::Server(address)) => legacy_server = Some(split_address(address)?),
+        None => {}
+    }
+
+    // build the launch data from the version details
+    let mut config = version_details.launch_config(&env);
+
+    // accounts that signed in play with their Minecraft profile,
+    // everyone else gets the UUID the game would give them in offline mode
+    let username = match auth::account(&username).await? {
+        Some(account) => {
+            config.auth_uuid = account.uuid;
+            config.auth_access_token = account.access_token;
+            config.auth_xuid = account.xuid;
+
+            if let Some(client_id) = &settings::get().auth.client_id {
+                config.clientid = client_id.clone();
+            }
+
+            config.user_type = "msa".to_string();
+
+            account.name
+        }
+        None => {
+            config.auth_uuid = auth::offline_uuid(&username);
+
+            username
+        }
+    };
+
+    if let Some((host, port)) = legacy_server {
+        config.game_args.extend([
+            "--server".to_string(),
+            host,
+            "--port".to_string(),
+            port.to_string(),
+        ]);
+    }
+
+    // fullscreen isn't a feature of the version JSON, every version accepts the argument
+    if options.fullscreen == Some(true) {
+        config.game_args.push("--fullscreen".to_string());
+    }
 
     // create the profile directory if it doesn't exist
     let profile_dir = profile_dir!(username);
     fs::create_dir_all(&profile_dir)?;
 
-    let mut cmd = format!(
-        "{java} {jvm_opts} {main} {game_args}",
-        java = java.unwrap_or(config.java),
-        jvm_opts = config.jvm_opts,
-        main = config.main,
-        game_args = config.game_args
-    );
-
-    // replace the variables in the command with the correct values from the config file
-    cmd = cmd
-        .replace("${natives_directory}", &config.natives_directory)
-        .replace("${launcher_name}", &config.launcher_name)
-        .replace("${launcher_version}", &config.launcher_version)
-        .replace("${log_path}", &config.log_path)
-        .replace("${classpath}", &config.classpath.join(":"))
-        .replace("${auth_player_name}", &username)
-        .replace("${version_name}", &version)
-        .replace("${game_directory}", &profile_dir)
-        .replace("${assets_root}", &config.assets_root)
-        .replace("${assets_index_name}", &config.assets_index_name)
-        .replace("${auth_uuid}", &config.auth_uuid.to_string())
-        .replace(
-            "${auth_access_token}",
-            &config.auth_access_token.to_string(),
+    // legacy versions read the assets from a directory tree built from the object store
+    let assets_index = AssetsIndex::load(&config.assets_index_name)?;
+    let game_assets = assets_index.game_assets(&config.assets_index_name, &profile_dir)?;
+
+    let classpath =
+        env::join_paths(&config.classpath).map_err(|e| anyhow!("Invalid classpath entry: {e}"))?;
+
+    let mut placeholders = Placeholders::default();
+    placeholders
+        .set("auth_player_name", &username)
+        .set("version_name", &version)
+        .set("version_type", &config.version_type)
+        .set("game_directory", &profile_dir)
+        .set("assets_root", &config.assets_root)
+        .set("assets_index_name", &config.assets_index_name)
+        .set("game_assets", &game_assets)
+        .set("auth_uuid", &config.auth_uuid)
+        .set("auth_access_token", &config.auth_access_token)
+        .set(
+            "auth_session",
+            format!(
+                "token:{token}:{uuid}",
+                token = config.auth_access_token,
+                uuid = config.auth_uuid
+            ),
+        )
+        .set("clientid", &config.clientid)
+        .set("auth_xuid", &config.auth_xuid)
+        .set("user_type", &config.user_type)
+        .set("user_properties", "{}")
+        .set("launcher_name", &config.launcher_name)
+        .set("launcher_version", &config.launcher_version)
+        .set("natives_directory", &config.natives_directory)
+        .set("library_directory", libraries_dir!())
+        .set("classpath", classpath)
+        .set("classpath_separator", if cfg!(windows) { ";" } else { ":" })
+        .set("path", &config.log_path)
+        .set(
+            "resolution_width",
+            options.width.unwrap_or(DEFAULT_WIDTH).to_string(),
         )
-        .replace("${clientid}", &config.clientid.to_string())
-        .replace("${auth_xuid}", &config.auth_xuid.to_string())
-        .replace("${user_type}", &config.user_type)
-        .replace("${version_type}", &config.version_type);
+        .set(
+            "resolution_height",
+            options.height.unwrap_or(DEFAULT_HEIGHT).to_string(),
+        );
 
-    let command_vec: Vec<&str> = cmd.split_whitespace().collect();
+    match quick_play {
+        Some(QuickPlay::World(world)) => {
+            placeholders.set("quickPlaySingleplayer", world);
+        }
+        Some(QuickPlay::Server(address)) => {
+            placeholders.set("quickPlayMultiplayer", address);
+        }
+        None => {}
+    }
 
-    Command::new(command_vec[0])
-        .args(&command_vec[1..])
+    // substitute every argument on its own, so values with spaces stay a single argument
+    let args = placeholders.substitute(
+        config
+            .jvm_opts
+            .iter()
+            .chain([&config.main])
+            .chain(&config.game_args),
+    )?;
+
+    // the Java installation that fits the version best, unless one was chosen
+    let java = match java {
+        Some(java) => java,
+        None => java::select(&config.java_component, config.java_major_version).await?,
+    };
+
+    Command::new(java)
+        .args(args)
         .current_dir(version_dir)
         .spawn()?
         .wait()?;
 
     Ok(())
 }
+
+/// Split a server address into the host and the port, the default port is used if it doesn't have one.
+fn split_address(address: &str) -> anyhow::Result<(String, u16)> {
+    // IPv6 addresses have to be in brackets to be followed by a port
+    let (host, port) = match address.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((host, "")) => (host, None),
+            Some((host, port)) => match port.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => return Err(anyhow!("Invalid server address {address}")),
+            },
+            None => return Err(anyhow!("Invalid server address {address}")),
+        },
+        None => match address.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (address, None),
+        },
+    };
+
+    let port = match port {
+        Some(port) => port
+            .parse()
+            .map_err(|_| anyhow!("Invalid port in server address {address}"))?,
+        None => DEFAULT_PORT,
+    };
+
+    Ok((host.to_string(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_with_port() {
+        assert_eq!(
+            split_address("mc.example.com:25570").unwrap(),
+            ("mc.example.com".to_string(), 25570)
+        );
+    }
+
+    #[test]
+    fn address_without_port() {
+        assert_eq!(
+            split_address("mc.example.com").unwrap(),
+            ("mc.example.com".to_string(), DEFAULT_PORT)
+        );
+    }
+
+    #[test]
+    fn ipv6_address() {
+        assert_eq!(
+            split_address("[2001:db8::1]:25570").unwrap(),
+            ("2001:db8::1".to_string(), 25570)
+        );
+        assert_eq!(
+            split_address("[2001:db8::1]").unwrap(),
+            ("2001:db8::1".to_string(), DEFAULT_PORT)
+        );
+    }
+
+    #[test]
+    fn invalid_addresses() {
+        assert!(split_address("mc.example.com:port").is_err());
+        assert!(split_address("mc.example.com:70000").is_err());
+        assert!(split_address("[2001:db8::1:25570").is_err());
+        assert!(split_address("[2001:db8::1]25570").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::Path;
+
+use crate::colors;
+use crate::config::{game_dir, libraries_dir};
+
+/// Move the libraries of versions installed with their own `versions/<id>/libraries` directory to the shared store.
+///
+/// Returns the number of migrated files.
+pub fn migrate() -> anyhow::Result<usize> {
+    let versions_dir = game_dir!("versions");
+
+    if !Path::new(&versions_dir).exists() {
+        return Ok(0);
+    }
+
+    let libraries_dir = libraries_dir!();
+    let mut migrated = 0;
+
+    for entry in fs::read_dir(&versions_dir)? {
+        let old_dir = entry?.path().join("libraries");
+
+        if old_dir.is_dir() {
+            migrated += move_tree(&old_dir, Path::new(&libraries_dir))?;
+        }
+    }
+
+    if migrated > 0 {
+        println!(
+            "{GREEN}Migrated {migrated} libraries to the shared libraries directory{RESET}",
+            GREEN = colors::GREEN,
+            RESET = colors::RESET
+        );
+    }
+
+    Ok(migrated)
+}
+
+/// Move every file under `from` to the same relative path under `to` and remove the emptied directories.
+///
+/// Files that already exist in `to` are kept, their checksum is checked on the next download or verify.
+fn move_tree(from: &Path, to: &Path) -> anyhow::Result<usize> {
+    if from.is_dir() {
+        let mut moved = 0;
+
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+
+            moved += move_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+
+        fs::remove_dir(from)?;
+
+        return Ok(moved);
+    }
+
+    if to.exists() {
+        fs::remove_file(from)?;
+    } else {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::rename(from, to)?;
+    }
+
+    Ok(1)
+}
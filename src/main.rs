@@ -1,14 +1,32 @@
 extern crate core;
 
+mod arguments;
+mod assets;
+mod auth;
+mod cache;
 mod colors;
 mod config;
 mod download;
+mod downloader;
+mod hash;
 mod http_client;
+mod java;
 mod launch;
+mod libraries;
+mod progress;
+mod prune;
+mod rules;
+mod runtime;
+mod server;
+mod settings;
+mod version;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::process::exit;
 
+use crate::launch::QuickPlay;
+use crate::settings::LaunchOptions;
+
 #[derive(Parser)]
 #[clap(
     name = "watercraft",
@@ -16,11 +34,60 @@ use std::process::exit;
     long_about = env!("CARGO_PKG_DESCRIPTION"),
     version = env!("CARGO_PKG_VERSION"),
 )]
-enum Cli {
+struct Cli {
+    #[clap(
+        help = "Proxy to use for all requests (http://, https://, socks5:// or socks5h://)",
+        long = "proxy",
+        global = true
+    )]
+    proxy: Option<String>,
+    #[clap(
+        help = "Comma separated list of hosts to connect to without the proxy",
+        long = "no-proxy",
+        value_delimiter = ',',
+        global = true
+    )]
+    no_proxy: Option<Vec<String>>,
+    #[clap(
+        help = "Don't access the network, use only cached metadata and installed files",
+        long = "offline",
+        global = true
+    )]
+    offline: bool,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    #[clap(about = "List the available minecraft versions")]
+    Versions {
+        #[clap(help = "Include snapshots and old versions", long = "all", short = 'a')]
+        all: bool,
+    },
     #[clap(about = "Download a minecraft version")]
     Download {
         #[clap(help = "The version to download")]
         version: String,
+        #[clap(
+            help = "Number of files to download at the same time",
+            long = "jobs",
+            short = 'j',
+            default_value_t = downloader::DEFAULT_JOBS
+        )]
+        jobs: usize,
+    },
+    #[clap(about = "Verify the files of a downloaded version and repair corrupt ones")]
+    Verify {
+        #[clap(help = "The version to verify")]
+        version: String,
+        #[clap(
+            help = "Number of files to download at the same time",
+            long = "jobs",
+            short = 'j',
+            default_value_t = downloader::DEFAULT_JOBS
+        )]
+        jobs: usize,
     },
     #[clap(about = "Launches the game")]
     Launch {
@@ -30,6 +97,102 @@ enum Cli {
         username: String,
         #[clap(help = "Path to java to use", long = "java")]
         java: Option<String>,
+        #[clap(help = "Width of the game window", long = "width")]
+        width: Option<u32>,
+        #[clap(help = "Height of the game window", long = "height")]
+        height: Option<u32>,
+        #[clap(
+            help = "Start the game in fullscreen",
+            long = "fullscreen",
+            overrides_with = "no_fullscreen"
+        )]
+        fullscreen: bool,
+        #[clap(
+            help = "Start the game in a window, even if the instance is set to fullscreen",
+            long = "no-fullscreen",
+            overrides_with = "fullscreen"
+        )]
+        no_fullscreen: bool,
+        #[clap(
+            help = "Start the game in demo mode",
+            long = "demo",
+            overrides_with = "no_demo"
+        )]
+        demo: bool,
+        #[clap(
+            help = "Start the full game, even if the instance is set to demo mode",
+            long = "no-demo",
+            overrides_with = "demo"
+        )]
+        no_demo: bool,
+        #[clap(
+            help = "Join a server (host:port) once the game has started",
+            long = "join",
+            conflicts_with = "world"
+        )]
+        join: Option<String>,
+        #[clap(
+            help = "Open a singleplayer world once the game has started",
+            long = "world"
+        )]
+        world: Option<String>,
+    },
+    #[clap(about = "Sign in with a Microsoft account")]
+    Login,
+    #[clap(about = "Remove a Microsoft account that signed in")]
+    Logout {
+        #[clap(help = "The username of the account")]
+        username: String,
+    },
+    #[clap(about = "Delete assets and libraries no installed version uses anymore")]
+    Prune {
+        #[clap(help = "Only show what would be deleted", long = "dry-run")]
+        dry_run: bool,
+    },
+    #[clap(about = "Manage dedicated servers")]
+    Server {
+        #[clap(subcommand)]
+        command: ServerCommand,
+    },
+    #[clap(about = "Manage Java installations")]
+    Java {
+        #[clap(subcommand)]
+        command: JavaCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum JavaCommand {
+    #[clap(about = "List the Java installations found on the system")]
+    List,
+}
+
+#[derive(Subcommand)]
+enum ServerCommand {
+    #[clap(about = "Install a dedicated server into a directory")]
+    Install {
+        #[clap(help = "The version to install")]
+        version: String,
+        #[clap(help = "The directory to install the server into")]
+        dir: String,
+        #[clap(
+            help = "Accept the Minecraft EULA without asking",
+            long = "accept-eula"
+        )]
+        accept_eula: bool,
+    },
+    #[clap(about = "Run a dedicated server")]
+    Run {
+        #[clap(help = "The directory of the server")]
+        dir: String,
+        #[clap(help = "Path to java to use", long = "java")]
+        java: Option<String>,
+        #[clap(
+            help = "Memory of the server, e.g. 2G or 512M",
+            long = "memory",
+            short = 'm'
+        )]
+        memory: Option<String>,
     },
 }
 
@@ -37,22 +200,137 @@ enum Cli {
 async fn main() {
     let args = Cli::parse();
 
-    match args {
-        Cli::Download { version } => {
-            if let Err(e) = download::download(version).await {
+    let mut settings = match settings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{red}{error}", red = colors::RED, error = e);
+            exit(1);
+        }
+    };
+
+    // command line options override the settings file
+    if args.proxy.is_some() {
+        settings.proxy.url = args.proxy;
+    }
+    if let Some(no_proxy) = args.no_proxy {
+        settings.proxy.no_proxy = no_proxy;
+    }
+    if args.offline {
+        settings.offline = true;
+    }
+
+    if let Err(e) = settings.proxy.proxy_url() {
+        eprintln!("{red}{error}", red = colors::RED, error = e);
+        exit(1);
+    }
+
+    settings::init(settings);
+
+    match args.command {
+        Command::Versions { all } => {
+            if let Err(e) = download::list_versions(all).await {
+                eprintln!("{red}{error}", red = colors::RED, error = e);
+                exit(1);
+            }
+        }
+        Command::Download { version, jobs } => {
+            if let Err(e) = download::download(version, jobs).await {
                 eprintln!("{red}{error}", red = colors::RED, error = e);
                 exit(1);
             }
         }
-        Cli::Launch {
+        Command::Verify { version, jobs } => {
+            if let Err(e) = download::verify(version, jobs).await {
+                eprintln!("{red}{error}", red = colors::RED, error = e);
+                exit(1);
+            }
+        }
+        Command::Launch {
             version,
             username,
             java,
+            width,
+            height,
+            fullscreen,
+            no_fullscreen,
+            demo,
+            no_demo,
+            join,
+            world,
         } => {
-            if let Err(e) = launch::launch(version, username, java).await {
+            let options = LaunchOptions {
+                width,
+                height,
+                fullscreen: flag(fullscreen, no_fullscreen),
+                demo: flag(demo, no_demo),
+            };
+
+            let quick_play = match (join, world) {
+                (Some(address), _) => Some(QuickPlay::Server(address)),
+                (_, Some(world)) => Some(QuickPlay::World(world)),
+                _ => None,
+            };
+
+            if let Err(e) = launch::launch(version, username, java, options, quick_play).await {
                 eprintln!("{red}Error: {error}", red = colors::RED, error = e);
                 exit(1);
             }
         }
+        Command::Login => {
+            if let Err(e) = auth::login().await {
+                eprintln!("{red}{error}", red = colors::RED, error = e);
+                exit(1);
+            }
+        }
+        Command::Logout { username } => {
+            if let Err(e) = auth::logout(&username) {
+                eprintln!("{red}{error}", red = colors::RED, error = e);
+                exit(1);
+            }
+        }
+        Command::Prune { dry_run } => {
+            if let Err(e) = prune::prune(dry_run) {
+                eprintln!("{red}{error}", red = colors::RED, error = e);
+                exit(1);
+            }
+        }
+        Command::Server {
+            command:
+                ServerCommand::Install {
+                    version,
+                    dir,
+                    accept_eula,
+                },
+        } => {
+            if let Err(e) = server::install(version, dir, accept_eula).await {
+                eprintln!("{red}{error}", red = colors::RED, error = e);
+                exit(1);
+            }
+        }
+        Command::Server {
+            command: ServerCommand::Run { dir, java, memory },
+        } => {
+            if let Err(e) = server::run(dir, java, memory).await {
+                eprintln!("{red}{error}", red = colors::RED, error = e);
+                exit(1);
+            }
+        }
+        Command::Java {
+            command: JavaCommand::List,
+        } => {
+            if let Err(e) = java::list() {
+                eprintln!("{red}{error}", red = colors::RED, error = e);
+                exit(1);
+            }
+        }
+    }
+}
+
+/// Get the value of a flag that can be turned on and off, `None` if neither was passed.
+fn flag(on: bool, off: bool) -> Option<bool> {
+    match (on, off) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
     }
 }
@@ -0,0 +1,179 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use byte_unit::Byte;
+use once_cell::sync::Lazy;
+
+use crate::colors;
+
+/// How often the progress line is redrawn.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Set while a progress line is shown, other output has to be printed above it.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Serializes writes to stdout, so log lines and the progress line don't get mixed.
+static OUTPUT: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Print a line, above the progress line if one is shown.
+pub fn println(line: &str) {
+    let _lock = OUTPUT.lock().unwrap();
+    let mut stdout = io::stdout().lock();
+
+    if ACTIVE.load(Ordering::Relaxed) {
+        // clear the progress line, it's drawn again on the next refresh
+        let _ = write!(stdout, "\r\x1b[2K");
+    }
+
+    let _ = writeln!(stdout, "{line}");
+}
+
+/// Progress of a batch of downloads.
+///
+/// On a terminal a single live line shows the totals, otherwise only a summary is printed once it's finished.
+#[derive(Debug)]
+pub struct Progress {
+    total_files: usize,
+    total_bytes: u64,
+    files: AtomicUsize,
+    bytes: AtomicU64,
+    transferred: AtomicU64,
+    start: Instant,
+    live: bool,
+    finished: AtomicBool,
+}
+
+impl Progress {
+    /// Start tracking the given number of files and bytes.
+    pub fn start(total_files: usize, total_bytes: u64) -> Arc<Self> {
+        let progress = Arc::new(Self {
+            total_files,
+            total_bytes,
+            files: AtomicUsize::new(0),
+            bytes: AtomicU64::new(0),
+            transferred: AtomicU64::new(0),
+            start: Instant::now(),
+            live: io::stdout().is_terminal(),
+            finished: AtomicBool::new(false),
+        });
+
+        if progress.live {
+            ACTIVE.store(true, Ordering::Relaxed);
+
+            let progress = progress.clone();
+
+            tokio::spawn(async move {
+                while !progress.finished.load(Ordering::Relaxed) {
+                    progress.draw();
+                    tokio::time::sleep(REFRESH_INTERVAL).await;
+                }
+            });
+        }
+
+        progress
+    }
+
+    /// Check if a live progress line is shown, per file messages are not needed then.
+    pub fn is_live(&self) -> bool {
+        self.live
+    }
+
+    /// Record bytes received from the network.
+    pub fn transferred(&self, bytes: u64) {
+        self.transferred.fetch_add(bytes, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Record bytes that didn't have to be downloaded (existing or resumed files).
+    pub fn skipped(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Remove bytes of a download that has to be started over.
+    pub fn discarded(&self, bytes: u64) {
+        self.bytes.fetch_sub(bytes, Ordering::Relaxed);
+    }
+
+    /// Record a finished file.
+    pub fn file_done(&self) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Stop the live progress line and print a summary.
+    pub fn finish(&self) {
+        self.finished.store(true, Ordering::Relaxed);
+
+        let _lock = OUTPUT.lock().unwrap();
+
+        if self.live {
+            ACTIVE.store(false, Ordering::Relaxed);
+            print!("\r\x1b[2K");
+        }
+
+        let transferred = self.transferred.load(Ordering::Relaxed);
+
+        println!(
+            "{GREEN}Done {files}/{total_files} files, downloaded {size} in {elapsed:.1}s{RESET}",
+            files = self.files.load(Ordering::Relaxed),
+            total_files = self.total_files,
+            size = format_bytes(transferred),
+            elapsed = self.start.elapsed().as_secs_f64(),
+            GREEN = colors::GREEN,
+            RESET = colors::RESET
+        );
+    }
+
+    /// Draw the progress line in place.
+    fn draw(&self) {
+        let _lock = OUTPUT.lock().unwrap();
+
+        if !ACTIVE.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let bytes = self.bytes.load(Ordering::Relaxed).min(self.total_bytes);
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = self.transferred.load(Ordering::Relaxed) as f64 / elapsed.max(0.001);
+
+        let eta = if rate > 0.0 {
+            format_duration((self.total_bytes - bytes) as f64 / rate)
+        } else {
+            "--:--".to_string()
+        };
+
+        let mut stdout = io::stdout().lock();
+        let _ = write!(
+            stdout,
+            "\r\x1b[2K{MAGENTA}[{files}/{total_files} files] {GREY}{bytes} / {total_bytes} {MAGENTA}{rate}/s {GREY}ETA {eta}{RESET}",
+            files = self.files.load(Ordering::Relaxed),
+            total_files = self.total_files,
+            bytes = format_bytes(bytes),
+            total_bytes = format_bytes(self.total_bytes),
+            rate = format_bytes(rate as u64),
+            MAGENTA = colors::MAGENTA,
+            GREY = colors::GREY,
+            RESET = colors::RESET
+        );
+        let _ = stdout.flush();
+    }
+}
+
+/// Format a number of bytes with the most appropriate unit.
+pub fn format_bytes(bytes: u64) -> String {
+    Byte::from_bytes(bytes.into())
+        .get_appropriate_unit(false)
+        .to_string()
+}
+
+/// Format a number of seconds as `m:ss` or `h:mm:ss`.
+fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+
+use crate::assets::{self, AssetsIndex};
+use crate::colors;
+use crate::config::{assets_dir, assets_indexes_dir, assets_objects_dir, game_dir, libraries_dir};
+use crate::progress::format_bytes;
+use crate::version::VersionDetails;
+
+/// Delete the assets, libraries and natives archives that no installed version references anymore.
+pub fn prune(dry_run: bool) -> anyhow::Result<()> {
+    let versions_dir = game_dir!("versions");
+    let libraries_dir = libraries_dir!();
+    let assets_indexes_dir = assets_indexes_dir!();
+    let assets_objects_dir = assets_objects_dir!();
+
+    // objects of older installs are stored by name, move them to where the assets indexes reference them
+    if !dry_run {
+        assets::migrate_objects()?;
+    }
+
+    let mut referenced = HashSet::new();
+    let mut indexes = HashSet::new();
+    let mut unknown = vec![];
+
+    if Path::new(&versions_dir).exists() {
+        for entry in fs::read_dir(&versions_dir)? {
+            let entry = entry?;
+
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            let version = entry.file_name().to_string_lossy().to_string();
+
+            // without the version details there is no way to know which files the version uses
+            let version_details = match VersionDetails::load(&version) {
+                Ok(version_details) => version_details,
+                Err(_) => {
+                    unknown.push(version);
+                    continue;
+                }
+            };
+
+            // libraries of every system, so installs shared with other systems are kept
+            for lib in &version_details.libraries {
+                let artifacts = lib
+                    .downloads
+                    .artifact
+                    .iter()
+                    .chain(lib.downloads.classifiers.iter().flat_map(|x| x.values()));
+
+                for artifact in artifacts {
+                    referenced.insert(PathBuf::from(format!(
+                        "{libraries_dir}/{path}",
+                        path = artifact.path
+                    )));
+                }
+            }
+
+            let index_id = version_details.asset_index.id;
+            referenced.insert(PathBuf::from(format!(
+                "{assets_indexes_dir}/{index_id}.json"
+            )));
+
+            // without the assets index every object of the version would look unreferenced
+            let assets_index = match AssetsIndex::load(&index_id) {
+                Ok(assets_index) => assets_index,
+                Err(_) => {
+                    unknown.push(version);
+                    continue;
+                }
+            };
+
+            for object in assets_index.objects.values() {
+                referenced.insert(PathBuf::from(format!(
+                    "{assets_objects_dir}/{path}",
+                    path = assets::object_path(&object.hash)
+                )));
+            }
+
+            indexes.insert(index_id);
+        }
+    }
+
+    if !unknown.is_empty() {
+        return Err(anyhow!(
+            "Can't find the files used by {versions}, download them again or remove them before pruning",
+            versions = unknown.join(", ")
+        ));
+    }
+
+    let mut removed = 0;
+    let mut reclaimed = 0;
+
+    let mut remove = |path: &Path, size: u64| -> anyhow::Result<()> {
+        if dry_run {
+            println!(
+                "{GREY}Would remove {path}{RESET}",
+                path = path.display(),
+                GREY = colors::GREY,
+                RESET = colors::RESET
+            );
+        } else if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+
+        removed += 1;
+        reclaimed += size;
+
+        Ok(())
+    };
+
+    for dir in [&libraries_dir, &assets_indexes_dir, &assets_objects_dir] {
+        for file in files(Path::new(dir))? {
+            // objects still stored by name are migrated instead of removed
+            if dir == &assets_objects_dir && !in_hash_layout(&file, &assets_objects_dir) {
+                continue;
+            }
+
+            if !referenced.contains(&file) {
+                let size = file.metadata()?.len();
+                remove(&file, size)?;
+            }
+        }
+
+        if !dry_run {
+            remove_empty_dirs(Path::new(dir))?;
+        }
+    }
+
+    // virtual directories of legacy assets indexes
+    let virtual_dir = assets_dir!("virtual");
+    if Path::new(&virtual_dir).exists() {
+        for entry in fs::read_dir(&virtual_dir)? {
+            let entry = entry?;
+
+            if !indexes.contains(&entry.file_name().to_string_lossy().to_string()) {
+                let size = files(&entry.path())?
+                    .iter()
+                    .map(|x| x.metadata().map(|x| x.len()))
+                    .sum::<Result<u64, _>>()?;
+
+                remove(&entry.path(), size)?;
+            }
+        }
+    }
+
+    let reclaimed = format_bytes(reclaimed);
+
+    if dry_run {
+        println!(
+            "{GREEN}Would remove {removed} unreferenced files and directories, reclaiming {reclaimed}{RESET}",
+            GREEN = colors::GREEN,
+            RESET = colors::RESET
+        );
+    } else {
+        println!(
+            "{GREEN}Removed {removed} unreferenced files and directories, reclaimed {reclaimed}{RESET}",
+            GREEN = colors::GREEN,
+            RESET = colors::RESET
+        );
+    }
+
+    Ok(())
+}
+
+/// Check if the given file in the object store is stored under a hash prefix directory.
+fn in_hash_layout(file: &Path, objects_dir: &str) -> bool {
+    file.strip_prefix(objects_dir)
+        .ok()
+        .and_then(|x| x.components().next())
+        .is_some_and(|x| assets::is_prefix_dir(&x.as_os_str().to_string_lossy()))
+}
+
+/// List every file under the given directory.
+fn files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut list = vec![];
+
+    if !dir.exists() {
+        return Ok(list);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_dir() {
+            list.extend(files(&entry.path())?);
+        } else {
+            list.push(entry.path());
+        }
+    }
+
+    Ok(list)
+}
+
+/// Remove the empty directories under the given directory, but not the directory itself.
+fn remove_empty_dirs(dir: &Path) -> anyhow::Result<()> {
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            remove_empty_dirs(&path)?;
+
+            if fs::read_dir(&path)?.next().is_none() {
+                fs::remove_dir(&path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
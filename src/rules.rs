@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::version::OS_NAME;
+
+/// A rule of a library or an argument in the version JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub action: RuleAction,
+    pub os: Option<RuleOs>,
+    pub features: Option<HashMap<String, bool>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleAction {
+    Allow,
+    Disallow,
+}
+
+/// The system a rule applies to, every field that is set has to match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleOs {
+    pub name: Option<String>,
+    pub arch: Option<String>,
+    /// Regex matched against the OS version.
+    pub version: Option<String>,
+}
+
+/// The system and launch features the rules are evaluated against.
+#[derive(Debug, Clone)]
+pub struct Environment {
+    /// OS name as used in the version JSON (`linux`, `osx` or `windows`).
+    pub os_name: String,
+    /// CPU architecture, `x86` is only used for 32-bit x86.
+    pub os_arch: String,
+    pub os_version: String,
+    /// Enabled launch features, like `is_demo_user` or `has_custom_resolution`.
+    pub features: HashMap<String, bool>,
+}
+
+/// The environment of this system, read once.
+static SYSTEM: Lazy<Environment> = Lazy::new(|| Environment {
+    os_name: OS_NAME.to_string(),
+    os_arch: std::env::consts::ARCH.to_string(),
+    os_version: os_version(),
+    features: HashMap::new(),
+});
+
+impl Environment {
+    /// The environment of this system, without any features enabled.
+    pub fn current() -> Self {
+        SYSTEM.clone()
+    }
+}
+
+impl Rule {
+    /// Check if the rule applies to the given environment.
+    pub fn matches(&self, env: &Environment) -> bool {
+        if let Some(os) = &self.os {
+            if os.name.as_ref().is_some_and(|x| *x != env.os_name) {
+                return false;
+            }
+
+            if os.arch.as_ref().is_some_and(|x| *x != env.os_arch) {
+                return false;
+            }
+
+            if let Some(version) = &os.version {
+                // a rule with an invalid regex can't match anything
+                match Regex::new(version) {
+                    Ok(regex) if regex.is_match(&env.os_version) => {}
+                    _ => return false,
+                }
+            }
+        }
+
+        // features that aren't set count as disabled
+        if let Some(features) = &self.features {
+            for (name, value) in features {
+                if env.features.get(name).copied().unwrap_or(false) != *value {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+}
+
+/// Evaluate a list of rules like the vanilla launcher does.
+///
+/// Without rules everything is allowed, otherwise it's disallowed unless a matching rule allows it and the last matching rule wins.
+pub fn allowed(rules: &[Rule], env: &Environment) -> bool {
+    if rules.is_empty() {
+        return true;
+    }
+
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.matches(env))
+        .is_some_and(|rule| rule.action == RuleAction::Allow)
+}
+
+/// Get the OS version, the same value Java reports as `os.version`.
+#[cfg(target_os = "linux")]
+fn os_version() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .map(|x| x.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Get the OS version, the same value Java reports as `os.version`.
+#[cfg(target_os = "macos")]
+fn os_version() -> String {
+    std::process::Command::new("sw_vers")
+        .arg("-productVersion")
+        .output()
+        .map(|x| String::from_utf8_lossy(&x.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+/// Get the OS version, the same value Java reports as `os.version`.
+#[cfg(target_os = "windows")]
+fn os_version() -> String {
+    // `ver` prints something like "Microsoft Windows [Version 10.0.19045.3570]"
+    std::process::Command::new("cmd")
+        .args(["/c", "ver"])
+        .output()
+        .map(|x| {
+            String::from_utf8_lossy(&x.stdout)
+                .split("Version ")
+                .nth(1)
+                .unwrap_or_default()
+                .trim_end_matches(|c: char| !c.is_ascii_digit())
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::{VersionDetails, VersionDetailsLibraries};
+
+    /// Trimmed copies of the vanilla version JSON, keeping the libraries and arguments with rules.
+    const V1_7_10: &str = include_str!("../tests/fixtures/1.7.10.json");
+    const V1_19_4: &str = include_str!("../tests/fixtures/1.19.4.json");
+
+    fn env(os_name: &str, os_arch: &str, os_version: &str, features: &[&str]) -> Environment {
+        Environment {
+            os_name: os_name.to_string(),
+            os_arch: os_arch.to_string(),
+            os_version: os_version.to_string(),
+            features: features.iter().map(|x| (x.to_string(), true)).collect(),
+        }
+    }
+
+    fn parse(json: &str) -> VersionDetails {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn library<'a>(details: &'a VersionDetails, name: &str) -> &'a VersionDetailsLibraries {
+        details.libraries.iter().find(|x| x.name == name).unwrap()
+    }
+
+    fn library_allowed(details: &VersionDetails, name: &str, env: &Environment) -> bool {
+        details.libraries(env).any(|x| x.name == name)
+    }
+
+    #[test]
+    fn no_rules_allow_everything() {
+        let details = parse(V1_7_10);
+
+        assert!(allowed(&[], &env("linux", "x86_64", "6.1.0", &[])));
+        assert!(library(&details, "com.mojang:realms:1.3.5").rules.is_none());
+        assert!(library_allowed(
+            &details,
+            "com.mojang:realms:1.3.5",
+            &env("windows", "x86", "6.1", &[])
+        ));
+    }
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let details = parse(V1_7_10);
+        let name = "org.lwjgl.lwjgl:lwjgl:2.9.1";
+
+        // allowed by the first rule, the osx rule doesn't match
+        assert!(library_allowed(
+            &details,
+            name,
+            &env("linux", "x86_64", "6.1.0", &[])
+        ));
+        // allowed by the first rule, disallowed by the second one
+        assert!(!library_allowed(
+            &details,
+            name,
+            &env("osx", "x86_64", "10.5.8", &[])
+        ));
+
+        // twitch has natives for every system except linux
+        let name = "tv.twitch:twitch-platform:5.16";
+        assert!(library_allowed(
+            &details,
+            name,
+            &env("windows", "x86", "6.1", &[])
+        ));
+        assert!(!library_allowed(
+            &details,
+            name,
+            &env("linux", "x86_64", "6.1.0", &[])
+        ));
+    }
+
+    #[test]
+    fn os_version_is_a_regex() {
+        let details = parse(V1_7_10);
+
+        // only OS X 10.5 is disallowed
+        assert!(library_allowed(
+            &details,
+            "org.lwjgl.lwjgl:lwjgl:2.9.1",
+            &env("osx", "x86_64", "10.9.5", &[])
+        ));
+        assert!(!library_allowed(
+            &details,
+            "org.lwjgl.lwjgl:lwjgl:2.9.1",
+            &env("osx", "x86_64", "10.5.8", &[])
+        ));
+
+        // and the older lwjgl is used there instead
+        assert!(library_allowed(
+            &details,
+            "org.lwjgl.lwjgl:lwjgl:2.9.1-nightly-20130708-debug3",
+            &env("osx", "x86_64", "10.5.8", &[])
+        ));
+        assert!(!library_allowed(
+            &details,
+            "org.lwjgl.lwjgl:lwjgl:2.9.1-nightly-20130708-debug3",
+            &env("linux", "x86_64", "6.1.0", &[])
+        ));
+    }
+
+    #[test]
+    fn no_matching_rule_disallows() {
+        let details = parse(V1_7_10);
+
+        assert!(library_allowed(
+            &details,
+            "tv.twitch:twitch-external-platform:4.5",
+            &env("windows", "x86", "6.1", &[])
+        ));
+        assert!(!library_allowed(
+            &details,
+            "tv.twitch:twitch-external-platform:4.5",
+            &env("linux", "x86_64", "6.1.0", &[])
+        ));
+
+        let details = parse(V1_19_4);
+
+        assert!(library_allowed(
+            &details,
+            "org.lwjgl:lwjgl:3.3.1:natives-linux",
+            &env("linux", "x86_64", "6.1.0", &[])
+        ));
+        assert!(!library_allowed(
+            &details,
+            "org.lwjgl:lwjgl:3.3.1:natives-windows",
+            &env("linux", "x86_64", "6.1.0", &[])
+        ));
+        assert!(!library_allowed(
+            &details,
+            "ca.weblite:java-objc-bridge:1.1",
+            &env("linux", "x86_64", "6.1.0", &[])
+        ));
+    }
+
+    #[test]
+    fn natives_by_arch() {
+        let details = parse(V1_19_4);
+        let arm = env("osx", "aarch64", "13.2", &[]);
+        let intel = env("osx", "x86_64", "13.2", &[]);
+
+        assert!(library_allowed(
+            &details,
+            "org.lwjgl:lwjgl:3.3.1:natives-macos-arm64",
+            &arm
+        ));
+        assert!(!library_allowed(
+            &details,
+            "org.lwjgl:lwjgl:3.3.1:natives-macos",
+            &arm
+        ));
+        assert!(library_allowed(
+            &details,
+            "org.lwjgl:lwjgl:3.3.1:natives-macos",
+            &intel
+        ));
+        assert!(!library_allowed(
+            &details,
+            "org.lwjgl:lwjgl:3.3.1:natives-macos-arm64",
+            &intel
+        ));
+
+        // `${arch}` in the natives map is replaced with 32 or 64
+        let details = parse(V1_7_10);
+        let twitch = library(&details, "tv.twitch:twitch-platform:5.16");
+
+        let natives = twitch.natives(&env("windows", "x86", "6.1", &[])).unwrap();
+        assert!(natives.path.ends_with("natives-windows-32.jar"));
+
+        let natives = twitch
+            .natives(&env("windows", "x86_64", "6.1", &[]))
+            .unwrap();
+        assert!(natives.path.ends_with("natives-windows-64.jar"));
+    }
+
+    #[test]
+    fn jvm_arguments_by_os_and_arch() {
+        let details = parse(V1_19_4);
+        let arguments = details.arguments.as_ref().unwrap();
+
+        let linux = arguments.jvm(&env("linux", "x86_64", "6.1.0", &[]));
+        assert!(linux.contains(&"-Djava.library.path=${natives_directory}".to_string()));
+        assert!(!linux.contains(&"-XstartOnFirstThread".to_string()));
+        assert!(!linux.contains(&"-Xss1M".to_string()));
+
+        let osx = arguments.jvm(&env("osx", "aarch64", "13.2", &[]));
+        assert_eq!(osx[0], "-XstartOnFirstThread");
+
+        let windows = arguments.jvm(&env("windows", "x86", "10.0", &[]));
+        assert!(windows.contains(&"-Xss1M".to_string()));
+        assert!(windows.contains(&"-Dos.name=Windows 10".to_string()));
+        assert!(windows.contains(&"-Dos.version=10.0".to_string()));
+
+        let windows_7 = arguments.jvm(&env("windows", "x86_64", "6.1", &[]));
+        assert!(!windows_7.contains(&"-Dos.name=Windows 10".to_string()));
+    }
+
+    #[test]
+    fn legacy_versions_get_default_jvm_arguments() {
+        let details = parse(V1_7_10);
+
+        let linux = details.jvm_arguments(&env("linux", "x86_64", "6.1.0", &[]));
+        assert_eq!(
+            linux,
+            [
+                "-Djava.library.path=${natives_directory}",
+                "-Dminecraft.launcher.brand=${launcher_name}",
+                "-Dminecraft.launcher.version=${launcher_version}",
+                "-cp",
+                "${classpath}",
+            ]
+        );
+
+        let osx = details.jvm_arguments(&env("osx", "x86_64", "10.9.5", &[]));
+        assert_eq!(osx[0], "-XstartOnFirstThread");
+
+        let windows = details.jvm_arguments(&env("windows", "x86", "10.0", &[]));
+        assert!(windows.contains(&"-Dos.name=Windows 10".to_string()));
+        assert!(windows.contains(&"-Xss1M".to_string()));
+    }
+
+    #[test]
+    fn game_arguments_by_feature() {
+        let details = parse(V1_19_4);
+        let arguments = details.arguments.as_ref().unwrap();
+
+        let plain = arguments.game(&env("linux", "x86_64", "6.1.0", &[]));
+        assert!(!plain.contains(&"--demo".to_string()));
+        assert!(!plain.contains(&"--width".to_string()));
+
+        let demo = arguments.game(&env("linux", "x86_64", "6.1.0", &["is_demo_user"]));
+        assert_eq!(demo.last().unwrap(), "--demo");
+
+        let resolution =
+            arguments.game(&env("linux", "x86_64", "6.1.0", &["has_custom_resolution"]));
+        assert!(resolution.ends_with(&[
+            "--width".to_string(),
+            "${resolution_width}".to_string(),
+            "--height".to_string(),
+            "${resolution_height}".to_string(),
+        ]));
+
+        // a feature that is explicitly disabled doesn't match a rule requiring it
+        let mut disabled = env("linux", "x86_64", "6.1.0", &[]);
+        disabled.features.insert("is_demo_user".to_string(), false);
+        assert!(!arguments.game(&disabled).contains(&"--demo".to_string()));
+    }
+
+    #[test]
+    fn invalid_version_regex_never_matches() {
+        let rules = serde_json::from_str::<Vec<Rule>>(
+            r#"[{"action": "allow"}, {"action": "disallow", "os": {"version": "(10"}}]"#,
+        )
+        .unwrap();
+
+        assert!(allowed(&rules, &env("osx", "x86_64", "10.5.8", &[])));
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::config::runtime_dir;
+use crate::downloader::{self, Summary, Task};
+use crate::{cache, colors};
+
+/// Java runtimes of every platform, as used by the vanilla launcher.
+pub const RUNTIME_MANIFEST: &str = "https://launchermeta.mojang.com/v1/products/java-runtime/2ec0cc96c44e5a76b9c8b7c39df7210883d12871/all.json";
+
+/// Name of the runtime manifest in the metadata cache.
+const RUNTIME_MANIFEST_CACHE: &str = "java-runtime/all.json";
+
+/// File in the runtime directory with the installed runtime version, written once every file is in place.
+const VERSION_FILE: &str = ".version";
+
+/// Runtimes of each component, keyed by the platform and the component name.
+type RuntimeManifest = HashMap<String, HashMap<String, Vec<RuntimeManifestEntry>>>;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuntimeManifestEntry {
+    pub manifest: RuntimeManifestDownload,
+    pub version: RuntimeManifestVersion,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuntimeManifestDownload {
+    pub sha1: String,
+    pub size: u64,
+    pub url: String,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuntimeManifestVersion {
+    pub name: String,
+    pub released: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuntimeFiles {
+    pub files: HashMap<String, RuntimeFile>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RuntimeFile {
+    File {
+        #[serde(default)]
+        executable: bool,
+        downloads: RuntimeFileDownloads,
+    },
+    Directory,
+    Link {
+        target: String,
+    },
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuntimeFileDownloads {
+    pub raw: RuntimeManifestDownload,
+}
+
+/// Get the name of this system in the runtime manifest, if Mojang has runtimes for it.
+pub fn platform() -> Option<&'static str> {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => Some("linux"),
+        ("linux", "x86") => Some("linux-i386"),
+        _ => None,
+    }
+}
+
+/// Get the path of the `java` executable of a managed runtime.
+pub fn java_path(component: &str) -> String {
+    format!(
+        "{runtime_dir}/bin/java",
+        runtime_dir = runtime_dir!(component)
+    )
+}
+
+/// Check if the runtime was installed completely.
+pub fn is_installed(component: &str) -> bool {
+    Path::new(&format!(
+        "{runtime_dir}/{VERSION_FILE}",
+        runtime_dir = runtime_dir!(component)
+    ))
+    .exists()
+}
+
+/// Check if Mojang has a runtime of the given component for this system.
+pub async fn is_available(component: &str) -> anyhow::Result<bool> {
+    Ok(manifest_entry(component).await?.is_some())
+}
+
+/// Get the runtime of the given component for this system from the runtime manifest.
+///
+/// Some components are listed without any runtime on some platforms, like `java-runtime-gamma` on `linux-i386`.
+async fn manifest_entry(component: &str) -> anyhow::Result<Option<RuntimeManifestEntry>> {
+    let platform = match platform() {
+        Some(platform) => platform,
+        None => return Ok(None),
+    };
+
+    let mut manifest =
+        cache::get_json::<RuntimeManifest>(RUNTIME_MANIFEST, RUNTIME_MANIFEST_CACHE).await?;
+
+    Ok(manifest
+        .remove(platform)
+        .and_then(|mut x| x.remove(component))
+        .and_then(|x| x.into_iter().next()))
+}
+
+/// A Java runtime to install, with the files it's made of.
+#[derive(Debug)]
+pub struct Runtime {
+    pub component: String,
+    pub version: String,
+    pub files: RuntimeFiles,
+}
+
+impl Runtime {
+    /// Get the runtime of the given component for this system, `None` if Mojang has none.
+    pub async fn load(component: &str) -> anyhow::Result<Option<Self>> {
+        let entry = match manifest_entry(component).await? {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        // the manifest has an entry only on supported platforms
+        let platform = platform().unwrap_or_default();
+
+        // the file list decides what gets installed and made executable, so it's verified like the files
+        let files = cache::get_json_verified::<RuntimeFiles>(
+            &entry.manifest.url,
+            &format!("java-runtime/{platform}/{component}.json"),
+            &entry.manifest.sha1,
+            entry.manifest.size,
+        )
+        .await?;
+
+        Ok(Some(Self {
+            component: component.to_string(),
+            version: entry.version.name,
+            files,
+        }))
+    }
+
+    /// Get the downloads of the runtime files, the directories are created and the runtime is marked incomplete
+    /// until [`Runtime::finish`] is called.
+    pub fn tasks(&self) -> anyhow::Result<Vec<Task>> {
+        println!(
+            "{MAGENTA}Installing Java runtime {component} {version} ...{RESET}",
+            component = self.component,
+            version = self.version,
+            MAGENTA = colors::MAGENTA,
+            RESET = colors::RESET
+        );
+
+        let runtime_dir = runtime_dir!(self.component);
+        let version_file = format!("{runtime_dir}/{VERSION_FILE}");
+
+        // the runtime is incomplete until every file is in place again
+        if Path::new(&version_file).exists() {
+            fs::remove_file(&version_file)?;
+        }
+
+        let mut tasks = vec![];
+
+        for (name, file) in &self.files.files {
+            let path = format!("{runtime_dir}/{name}");
+
+            match file {
+                RuntimeFile::File { downloads, .. } => tasks.push(Task::save(
+                    downloads.raw.url.clone(),
+                    downloads.raw.size,
+                    downloads.raw.sha1.clone(),
+                    path,
+                )),
+                RuntimeFile::Directory => fs::create_dir_all(&path)?,
+                RuntimeFile::Link { .. } => {}
+            }
+        }
+
+        Ok(tasks)
+    }
+
+    /// Set the permissions and create the links once the files are downloaded, and mark the runtime complete.
+    pub fn finish(&self) -> anyhow::Result<()> {
+        let runtime_dir = runtime_dir!(self.component);
+
+        // the permissions and links aren't part of the downloads
+        for (name, file) in &self.files.files {
+            let path = format!("{runtime_dir}/{name}");
+
+            match file {
+                RuntimeFile::File {
+                    executable: true, ..
+                } => set_executable(&path)?,
+                RuntimeFile::Link { target } => link(target, &path)?,
+                _ => {}
+            }
+        }
+
+        fs::write(format!("{runtime_dir}/{VERSION_FILE}"), &self.version)?;
+
+        Ok(())
+    }
+}
+
+/// Download the given Java runtime component to `runtimes/<component>`, skipping the files that are already valid.
+pub async fn install(component: &str, jobs: usize) -> anyhow::Result<Summary> {
+    let runtime = Runtime::load(component).await?.ok_or_else(|| {
+        anyhow!("Mojang has no Java runtime {component} for this system, use --java to choose one")
+    })?;
+
+    let summary = downloader::run(runtime.tasks()?, jobs).await?;
+    runtime.finish()?;
+
+    Ok(summary)
+}
+
+/// Make a file executable.
+#[cfg(unix)]
+fn set_executable(path: &str) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &str) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// Create a symbolic link, replacing whatever is at its path.
+#[cfg(unix)]
+fn link(target: &str, path: &str) -> anyhow::Result<()> {
+    if fs::symlink_metadata(path).is_ok() {
+        fs::remove_file(path)?;
+    }
+
+    if let Some(dir) = Path::new(path).parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    std::os::unix::fs::symlink(target, path)?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn link(_target: &str, _path: &str) -> anyhow::Result<()> {
+    Ok(())
+}
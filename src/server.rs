@@ -0,0 +1,188 @@
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::downloader::{self, Task};
+use crate::{colors, download, java};
+
+const EULA_URL: &str = "https://aka.ms/MinecraftEULA";
+
+/// Name of the file in the server directory that stores the installed version and the launch settings.
+const SERVER_CONFIG: &str = "watercraft-server.toml";
+
+const DEFAULT_MEMORY: &str = "2G";
+
+const DEFAULT_SERVER_PROPERTIES: &str = "#Minecraft server properties
+motd=A Minecraft Server
+server-port=25565
+level-name=world
+gamemode=survival
+difficulty=easy
+max-players=20
+online-mode=true
+pvp=true
+view-distance=10
+";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub version: String,
+    /// Path of the `java` executable, the one the version needs is chosen if it's not set.
+    #[serde(default)]
+    pub java: Option<String>,
+    pub memory: String,
+}
+
+/// Download the server jar of the given version into the server directory and prepare it to run.
+pub async fn install(version: String, dir: String, accept_eula: bool) -> anyhow::Result<()> {
+    println!("Installing server {version} in {dir} ...");
+
+    let (_, version_details) = download::version_details(&version).await?;
+
+    let server = match version_details.downloads.server {
+        Some(server) => server,
+        None => return Err(anyhow!("Version {version} has no server download")),
+    };
+
+    fs::create_dir_all(&dir)?;
+
+    downloader::run(
+        vec![Task::save(
+            server.url,
+            server.size.into(),
+            server.sha1,
+            format!("{dir}/server.jar"),
+        )],
+        downloader::DEFAULT_JOBS,
+    )
+    .await?;
+
+    // keep the launch settings of an existing install
+    let config_path = format!("{dir}/{SERVER_CONFIG}");
+    let config = match fs::read_to_string(&config_path) {
+        Ok(config) => ServerConfig {
+            version: version.clone(),
+            ..toml::from_str(&config)?
+        },
+        Err(_) => ServerConfig {
+            version: version.clone(),
+            java: None,
+            memory: DEFAULT_MEMORY.to_string(),
+        },
+    };
+    fs::write(&config_path, toml::to_string(&config)?)?;
+
+    let properties_path = format!("{dir}/server.properties");
+    if !Path::new(&properties_path).exists() {
+        fs::write(&properties_path, DEFAULT_SERVER_PROPERTIES)?;
+    }
+
+    if accept_eula || ask_eula()? {
+        fs::write(format!("{dir}/eula.txt"), format!("#By changing the setting below to TRUE you are indicating your agreement to our EULA ({EULA_URL}).\neula=true\n"))?;
+    } else {
+        println!(
+            "{RED}The EULA was not accepted, the server won't start until eula=true is set in {dir}/eula.txt{RESET}",
+            RED = colors::RED,
+            RESET = colors::RESET
+        );
+    }
+
+    println!(
+        "{GREEN}Server {version} installed in {dir}{RESET}",
+        GREEN = colors::GREEN,
+        RESET = colors::RESET
+    );
+
+    Ok(())
+}
+
+/// Start the server installed in the given directory.
+pub async fn run(dir: String, java: Option<String>, memory: Option<String>) -> anyhow::Result<()> {
+    let config_path = format!("{dir}/{SERVER_CONFIG}");
+
+    // if the config file doesn't exist, the server wasn't installed by watercraft
+    if !Path::new(&config_path).exists() {
+        return Err(anyhow!(
+            "No server is installed in {dir}, install one with `watercraft server install <version> {dir}`"
+        ));
+    }
+
+    let config: ServerConfig = toml::from_str(&fs::read_to_string(&config_path)?)?;
+
+    let memory = memory.unwrap_or(config.memory);
+    check_memory(&memory)?;
+
+    // the Java installation that fits the version best, unless one was chosen or set in the config
+    let java = match java.or(config.java) {
+        Some(java) => java,
+        None => {
+            let (_, version_details) = download::version_details(&config.version).await?;
+            let java_version = version_details.java_version();
+
+            java::select(&java_version.component, java_version.major_version).await?
+        }
+    };
+
+    println!("Starting server {version} ...", version = config.version);
+
+    Command::new(java)
+        .arg(format!("-Xms{memory}"))
+        .arg(format!("-Xmx{memory}"))
+        .args(["-jar", "server.jar", "nogui"])
+        .current_dir(dir)
+        .spawn()?
+        .wait()?;
+
+    Ok(())
+}
+
+/// Check that the memory is a size the JVM accepts, a number with an optional `K`, `M`, `G` or `T` suffix.
+fn check_memory(memory: &str) -> anyhow::Result<()> {
+    let digits = memory.trim_end_matches(|c: char| "kKmMgGtT".contains(c));
+
+    if digits.is_empty()
+        || memory.len() - digits.len() > 1
+        || !digits.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(anyhow!(
+            "Invalid memory {memory}, use a number of bytes with an optional K, M, G or T suffix, like 2G or 512M"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Ask the user to accept the EULA, it's never accepted if stdin isn't a terminal.
+fn ask_eula() -> anyhow::Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    print!("Do you accept the Minecraft EULA ({EULA_URL})? [y/N] ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_sizes() {
+        for memory in ["2G", "512M", "512m", "1048576", "4096k"] {
+            assert!(check_memory(memory).is_ok(), "{memory}");
+        }
+
+        for memory in ["", "G", "2GB", "2 G", "1.5G", "-2G"] {
+            assert!(check_memory(memory).is_err(), "{memory}");
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use once_cell::sync::OnceCell;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::config::game_dir;
+
+static SETTINGS: OnceCell<Settings> = OnceCell::new();
+
+/// Launcher settings, read from `~/.minecraft/watercraft.toml`.
+///
+/// Every field is optional in the file, missing ones use the default value.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub http: HttpSettings,
+    pub proxy: ProxySettings,
+    /// Mirrors to use instead of the official servers, keyed by the URL prefix they replace.
+    pub mirrors: HashMap<String, Vec<String>>,
+    /// Never access the network, only cached metadata and installed files are used.
+    pub offline: bool,
+    /// Launch options of each instance, keyed by the version.
+    pub instances: HashMap<String, LaunchOptions>,
+    pub auth: AuthSettings,
+}
+
+/// Timeouts and retry policy of the HTTP client.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpSettings {
+    /// Seconds to wait for a connection to be established.
+    pub connect_timeout: u64,
+    /// Seconds to wait for the response headers or the next chunk of the body.
+    pub read_timeout: u64,
+    /// How many times a failed GET request is retried.
+    pub retries: u32,
+    /// Milliseconds to wait before the first retry, doubled on every next one.
+    pub retry_delay: u64,
+    /// Maximum milliseconds to wait between two retries.
+    pub max_retry_delay: u64,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout: 10,
+            read_timeout: 30,
+            retries: 5,
+            retry_delay: 500,
+            max_retry_delay: 30_000,
+        }
+    }
+}
+
+/// Microsoft account login, the endpoints can be changed to test against other servers.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthSettings {
+    /// Client ID of the Azure application used to sign in, it needs the `XboxLive.signin` permission.
+    pub client_id: Option<String>,
+    pub device_code_url: String,
+    pub token_url: String,
+    pub xbox_live_url: String,
+    pub xsts_url: String,
+    pub minecraft_login_url: String,
+    pub entitlements_url: String,
+    pub profile_url: String,
+}
+
+impl Default for AuthSettings {
+    fn default() -> Self {
+        Self {
+            client_id: None,
+            device_code_url: "https://login.microsoftonline.com/consumers/oauth2/v2.0/devicecode"
+                .to_string(),
+            token_url: "https://login.microsoftonline.com/consumers/oauth2/v2.0/token".to_string(),
+            xbox_live_url: "https://user.auth.xboxlive.com/user/authenticate".to_string(),
+            xsts_url: "https://xsts.auth.xboxlive.com/xsts/authorize".to_string(),
+            minecraft_login_url: "https://api.minecraftservices.com/authentication/login_with_xbox"
+                .to_string(),
+            entitlements_url: "https://api.minecraftservices.com/entitlements/mcstore".to_string(),
+            profile_url: "https://api.minecraftservices.com/minecraft/profile".to_string(),
+        }
+    }
+}
+
+/// Proxy used for every request of the launcher.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxySettings {
+    /// URL of the proxy (`http://`, `https://`, `socks5://` or `socks5h://`), no proxy is used if it's not set.
+    pub url: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Hosts connected to without the proxy, a leading dot matches all subdomains and `*` matches every host.
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxySettings {
+    /// Get the proxy URL with the credentials, if a proxy is configured.
+    pub fn proxy_url(&self) -> anyhow::Result<Option<Url>> {
+        let url = match &self.url {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+
+        let mut url = Url::parse(url).map_err(|e| anyhow!("Invalid proxy URL {url}: {e}"))?;
+
+        if !["http", "https", "socks5", "socks5h"].contains(&url.scheme()) {
+            return Err(anyhow!(
+                "Unsupported proxy scheme {scheme}, use http, https, socks5 or socks5h",
+                scheme = url.scheme()
+            ));
+        }
+
+        if let Some(username) = &self.username {
+            url.set_username(username)
+                .map_err(|_| anyhow!("Invalid proxy URL {url}"))?;
+            url.set_password(self.password.as_deref())
+                .map_err(|_| anyhow!("Invalid proxy URL {url}"))?;
+        }
+
+        Ok(Some(url))
+    }
+
+    /// Check if the given host has to be connected to without the proxy.
+    pub fn bypass(&self, host: &str) -> bool {
+        let host = host.to_lowercase();
+
+        self.no_proxy.iter().any(|entry| {
+            let entry = entry.trim().to_lowercase();
+
+            if entry == "*" {
+                return true;
+            }
+
+            match entry.strip_prefix('.') {
+                Some(domain) => host == domain || host.ends_with(&entry),
+                None => host == entry || host.ends_with(&format!(".{entry}")),
+            }
+        })
+    }
+}
+
+/// Options of the game window and mode, set per instance or with the options of the `launch` command.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchOptions {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fullscreen: Option<bool>,
+    pub demo: Option<bool>,
+}
+
+impl LaunchOptions {
+    /// Use the options that aren't set here from the other ones.
+    pub fn or(self, other: &LaunchOptions) -> Self {
+        Self {
+            width: self.width.or(other.width),
+            height: self.height.or(other.height),
+            fullscreen: self.fullscreen.or(other.fullscreen),
+            demo: self.demo.or(other.demo),
+        }
+    }
+}
+
+/// Read the settings file, if it doesn't exist the default settings are used.
+pub fn load() -> anyhow::Result<Settings> {
+    let path = game_dir!("watercraft.toml");
+
+    if !Path::new(&path).exists() {
+        return Ok(Settings::default());
+    }
+
+    let settings = toml::from_str(&fs::read_to_string(&path)?)
+        .map_err(|e| anyhow!("Failed to parse {path}: {e}"))?;
+
+    Ok(settings)
+}
+
+/// Set the settings used by the rest of the launcher, it can be called only once.
+pub fn init(settings: Settings) {
+    SETTINGS
+        .set(settings)
+        .expect("settings are already initialized");
+}
+
+/// Get the launcher settings, the default ones are used if they weren't initialized.
+pub fn get() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_proxy(entries: &[&str]) -> ProxySettings {
+        ProxySettings {
+            no_proxy: entries.iter().map(|x| x.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn proxy(url: &str) -> ProxySettings {
+        ProxySettings {
+            url: Some(url.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn bypass_exact_host_and_subdomains() {
+        let proxy = no_proxy(&["example.com"]);
+
+        assert!(proxy.bypass("example.com"));
+        assert!(proxy.bypass("libraries.example.com"));
+        assert!(!proxy.bypass("notexample.com"));
+        assert!(!proxy.bypass("example.com.evil.net"));
+    }
+
+    #[test]
+    fn bypass_leading_dot() {
+        let proxy = no_proxy(&[".example.com"]);
+
+        assert!(proxy.bypass("example.com"));
+        assert!(proxy.bypass("a.b.example.com"));
+        assert!(!proxy.bypass("notexample.com"));
+    }
+
+    #[test]
+    fn bypass_ignores_case_and_whitespace() {
+        let proxy = no_proxy(&[" Example.COM "]);
+
+        assert!(proxy.bypass("EXAMPLE.com"));
+        assert!(proxy.bypass("Resources.Example.Com"));
+    }
+
+    #[test]
+    fn bypass_everything_or_nothing() {
+        assert!(no_proxy(&["*"]).bypass("piston-data.mojang.com"));
+        assert!(!no_proxy(&[]).bypass("piston-data.mojang.com"));
+        assert!(!no_proxy(&["localhost"]).bypass("piston-data.mojang.com"));
+    }
+
+    #[test]
+    fn proxy_schemes() {
+        assert!(ProxySettings::default().proxy_url().unwrap().is_none());
+
+        for url in [
+            "http://proxy:3128",
+            "https://proxy:3128",
+            "socks5://proxy:1080",
+            "socks5h://proxy:1080",
+        ] {
+            assert!(proxy(url).proxy_url().unwrap().is_some(), "{url}");
+        }
+
+        let err = proxy("ftp://proxy:21").proxy_url().unwrap_err();
+        assert!(err.to_string().starts_with("Unsupported proxy scheme ftp"));
+        assert!(proxy("not a url").proxy_url().is_err());
+    }
+
+    #[test]
+    fn proxy_credentials() {
+        let settings = ProxySettings {
+            username: Some("steve".to_string()),
+            password: Some("p@ss word".to_string()),
+            ..proxy("socks5://proxy:1080")
+        };
+
+        let url = settings.proxy_url().unwrap().unwrap();
+        assert_eq!(url.username(), "steve");
+        assert_eq!(url.password(), Some("p%40ss%20word"));
+        assert_eq!(url.host_str(), Some("proxy"));
+
+        let no_password = ProxySettings {
+            username: Some("steve".to_string()),
+            ..proxy("http://proxy:3128")
+        };
+        assert_eq!(
+            no_password.proxy_url().unwrap().unwrap().as_str(),
+            "http://steve@proxy:3128/"
+        );
+    }
+}
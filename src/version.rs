@@ -0,0 +1,390 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{assets_dir, libraries_dir, version_dir, Config};
+use crate::rules::{self, Environment, Rule};
+
+pub const MAINLINE_VERSIONS: &str = "https://launchermeta.mojang.com/mc/game/version_manifest.json";
+
+#[cfg(target_os = "linux")]
+pub const OS_NAME: &str = "linux";
+#[cfg(target_os = "windows")]
+pub const OS_NAME: &str = "windows";
+#[cfg(target_os = "macos")]
+pub const OS_NAME: &str = "osx";
+
+/// JVM arguments of versions that don't declare their own, as used by the vanilla launcher.
+static LEGACY_JVM_ARGUMENTS: Lazy<Vec<VersionDetailsArgument>> = Lazy::new(|| {
+    serde_json::from_str(
+        r#"[
+            {"rules": [{"action": "allow", "os": {"name": "osx"}}], "value": "-XstartOnFirstThread"},
+            {"rules": [{"action": "allow", "os": {"name": "windows"}}], "value": "-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump"},
+            {"rules": [{"action": "allow", "os": {"name": "windows", "version": "^10\\."}}], "value": ["-Dos.name=Windows 10", "-Dos.version=10.0"]},
+            {"rules": [{"action": "allow", "os": {"arch": "x86"}}], "value": "-Xss1M"},
+            "-Djava.library.path=${natives_directory}",
+            "-Dminecraft.launcher.brand=${launcher_name}",
+            "-Dminecraft.launcher.version=${launcher_version}",
+            "-cp",
+            "${classpath}"
+        ]"#,
+    )
+    .unwrap()
+});
+
+/// Game arguments of the launch features, for versions from before 1.13 that don't declare them.
+static LEGACY_GAME_ARGUMENTS: Lazy<Vec<VersionDetailsArgument>> = Lazy::new(|| {
+    serde_json::from_str(
+        r#"[
+            {"rules": [{"action": "allow", "features": {"is_demo_user": true}}], "value": "--demo"},
+            {"rules": [{"action": "allow", "features": {"has_custom_resolution": true}}], "value": ["--width", "${resolution_width}", "--height", "${resolution_height}"]}
+        ]"#,
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionManifest {
+    pub latest: VersionManifestLatest,
+    pub versions: Vec<VersionManifestVersions>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionManifestLatest {
+    pub release: String,
+    pub snapshot: String,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionManifestVersions {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub url: String,
+    pub time: String,
+    #[serde(rename = "releaseTime")]
+    pub release_time: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetails {
+    pub id: String,
+    pub assets: String,
+    #[serde(rename = "mainClass")]
+    pub main_class: String,
+    pub arguments: Option<VersionDetailsArguments>,
+    #[serde(rename = "minecraftArguments")]
+    pub minecraft_arguments: Option<String>,
+    #[serde(rename = "assetIndex")]
+    pub asset_index: VersionDetailsAssetIndex,
+    #[serde(rename = "javaVersion")]
+    pub java_version: Option<VersionDetailsJavaVersion>,
+    pub downloads: VersionDetailsDownloads,
+    pub libraries: Vec<VersionDetailsLibraries>,
+    pub logging: Option<VersionDetailsLogging>,
+    #[serde(rename = "releaseTime")]
+    pub release_time: String,
+    pub time: String,
+    #[serde(rename = "type")]
+    pub typ: String,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsArguments {
+    pub game: Vec<VersionDetailsArgument>,
+    #[serde(default)]
+    pub jvm: Vec<VersionDetailsArgument>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum VersionDetailsArgument {
+    String(String),
+    Rule(VersionDetailsArgumentCustom),
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsArgumentCustom {
+    pub rules: Vec<Rule>,
+    pub value: VersionDetailsArgumentValue,
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum VersionDetailsArgumentValue {
+    String(String),
+    List(Vec<String>),
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionDetailsJavaVersion {
+    pub component: String,
+    #[serde(rename = "majorVersion")]
+    pub major_version: u32,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsDownloads {
+    pub client: VersionDetailsDownloadsEntry,
+    pub server: Option<VersionDetailsDownloadsEntry>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsDownloadsEntry {
+    pub sha1: String,
+    pub size: u32,
+    pub url: String,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsLibraries {
+    pub name: String,
+    pub downloads: VersionDetailsLibrariesDownloads,
+    pub rules: Option<Vec<Rule>>,
+    pub natives: Option<HashMap<String, String>>,
+    pub extract: Option<VersionDetailsLibrariesExtract>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsLibrariesDownloads {
+    pub artifact: Option<VersionDetailsLibrariesDownloadsArtifact>,
+    pub classifiers: Option<HashMap<String, VersionDetailsLibrariesDownloadsArtifact>>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsLibrariesDownloadsArtifact {
+    pub path: String,
+    pub sha1: String,
+    pub size: u32,
+    pub url: String,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsLibrariesExtract {
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsLogging {
+    pub client: VersionDetailsLoggingClient,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsLoggingClient {
+    pub argument: String,
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub file: VersionDetailsLoggingClientFile,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsLoggingClientFile {
+    pub id: String,
+    pub sha1: String,
+    pub size: u32,
+    pub url: String,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionDetailsAssetIndex {
+    pub id: String,
+    pub sha1: String,
+    pub size: u32,
+    #[serde(rename = "totalSize")]
+    pub total_size: u32,
+    pub url: String,
+}
+
+impl VersionDetails {
+    /// Read the version JSON (`versions/<id>/<id>.json`) of an installed version.
+    pub fn load(version: &str) -> anyhow::Result<Self> {
+        let path = format!(
+            "{version_dir}/{version}.json",
+            version_dir = version_dir!(version)
+        );
+
+        if !Path::new(&path).exists() {
+            return Err(anyhow!(
+                "Version {version} has no {version}.json, download it again with `watercraft download {version}`"
+            ));
+        }
+
+        serde_json::from_slice(&fs::read(&path)?)
+            .map_err(|e| anyhow!("Failed to parse {path}: {e}"))
+    }
+
+    /// Get the libraries whose rules allow them in the given environment.
+    pub fn libraries<'a>(
+        &'a self,
+        env: &'a Environment,
+    ) -> impl Iterator<Item = &'a VersionDetailsLibraries> {
+        self.libraries.iter().filter(|lib| {
+            rules::allowed(lib.rules.as_deref().unwrap_or_default(), env) && lib.built_for(env)
+        })
+    }
+
+    /// Build the data needed to launch this version.
+    pub fn launch_config(&self, env: &Environment) -> Config {
+        let mut classpath = self
+            .libraries(env)
+            .filter_map(|lib| lib.downloads.artifact.as_ref())
+            .map(|artifact| {
+                format!(
+                    "{libraries_dir}/{lib_path}",
+                    libraries_dir = libraries_dir!(),
+                    lib_path = artifact.path
+                )
+            })
+            .collect::<Vec<String>>();
+
+        classpath.push(format!("{id}.jar", id = self.id));
+
+        let game_args = match &self.minecraft_arguments {
+            Some(minecraft_arguments) => {
+                // the placeholders are only substituted after splitting, so values can contain spaces
+                let mut args = minecraft_arguments
+                    .split_whitespace()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>();
+
+                args.extend(evaluate(&LEGACY_GAME_ARGUMENTS, env));
+
+                args
+            }
+            None => match &self.arguments {
+                Some(arguments) => arguments.game(env),
+                None => vec![],
+            },
+        };
+
+        let mut jvm_opts = self.jvm_arguments(env);
+
+        // old versions don't have a logging configuration
+        let log_path = match &self.logging {
+            Some(logging) => {
+                jvm_opts.push(logging.client.argument.clone());
+
+                format!("logging-{id}", id = logging.client.file.id)
+            }
+            None => "".to_string(),
+        };
+
+        let java_version = self.java_version();
+
+        Config {
+            version: self.id.clone(),
+            assets_root: assets_dir!(),
+            auth_uuid: "0".to_string(),
+            auth_access_token: "0".to_string(),
+            clientid: "0".to_string(),
+            auth_xuid: "0".to_string(),
+            version_type: self.typ.clone(),
+            user_type: "legacy".to_string(),
+            launcher_name: "minecraft-launcher".to_string(),
+            launcher_version: "2.1.1349".to_string(),
+            main: self.main_class.clone(),
+            assets_index_name: self.asset_index.id.clone(),
+            natives_directory: "libraries-natives".to_string(),
+            log_path,
+            classpath,
+            java_component: java_version.component,
+            java_major_version: java_version.major_version,
+            jvm_opts,
+            game_args,
+        }
+    }
+
+    /// Get the Java runtime the version needs, versions that don't declare one run on Java 8 (`jre-legacy`).
+    pub fn java_version(&self) -> VersionDetailsJavaVersion {
+        self.java_version
+            .clone()
+            .unwrap_or_else(|| VersionDetailsJavaVersion {
+                component: "jre-legacy".to_string(),
+                major_version: 8,
+            })
+    }
+
+    /// Check if any game argument of the version is gated behind the given feature.
+    pub fn has_feature(&self, feature: &str) -> bool {
+        let arguments = match &self.arguments {
+            Some(arguments) => arguments,
+            None => return false,
+        };
+
+        arguments.game.iter().any(|arg| match arg {
+            VersionDetailsArgument::Rule(custom) => custom.rules.iter().any(|rule| {
+                rule.features
+                    .as_ref()
+                    .is_some_and(|x| x.contains_key(feature))
+            }),
+            VersionDetailsArgument::String(_) => false,
+        })
+    }
+
+    /// Get the JVM arguments allowed in the given environment.
+    ///
+    /// Versions from before 1.13 only have `minecraftArguments`, they get the same defaults the vanilla launcher uses.
+    pub fn jvm_arguments(&self, env: &Environment) -> Vec<String> {
+        match &self.arguments {
+            Some(arguments) if !arguments.jvm.is_empty() => arguments.jvm(env),
+            _ => evaluate(&LEGACY_JVM_ARGUMENTS, env),
+        }
+    }
+}
+
+impl VersionDetailsLibraries {
+    /// Get the natives archive to extract for the given environment, for libraries with a `natives` map (before 1.19).
+    pub fn natives(&self, env: &Environment) -> Option<&VersionDetailsLibrariesDownloadsArtifact> {
+        let classifier = self.natives.as_ref()?.get(&env.os_name)?;
+
+        // some natives are built for 32 and 64-bit systems
+        let bits = match env.os_arch.as_str() {
+            "x86" | "arm" => "32",
+            _ => "64",
+        };
+        let classifier = classifier.replace("${arch}", bits);
+
+        self.downloads.classifiers.as_ref()?.get(&classifier)
+    }
+
+    /// Check if the library is built for the CPU architecture of the given environment.
+    ///
+    /// Since 1.19 natives are separate libraries with the OS and architecture in the classifier, like
+    /// `org.lwjgl:lwjgl:3.3.1:natives-linux-arm64`, the rules only check the OS name.
+    fn built_for(&self, env: &Environment) -> bool {
+        let classifier = match self.name.split(':').nth(3) {
+            Some(classifier) if classifier.starts_with("natives-") => classifier,
+            _ => return true,
+        };
+
+        // natives without an architecture suffix are built for x86_64
+        let arch = match classifier.rsplit('-').next() {
+            Some("arm64" | "aarch_64") => "aarch64",
+            Some("arm32") => "arm",
+            Some("x86") => "x86",
+            _ => "x86_64",
+        };
+
+        arch == env.os_arch
+    }
+}
+
+impl VersionDetailsArguments {
+    /// Get the game arguments allowed in the given environment.
+    pub fn game(&self, env: &Environment) -> Vec<String> {
+        evaluate(&self.game, env)
+    }
+
+    /// Get the JVM arguments allowed in the given environment.
+    pub fn jvm(&self, env: &Environment) -> Vec<String> {
+        evaluate(&self.jvm, env)
+    }
+}
+
+/// Flatten a list of arguments, keeping the values of rule-gated entries only if their rules allow them.
+fn evaluate(arguments: &[VersionDetailsArgument], env: &Environment) -> Vec<String> {
+    let mut args = vec![];
+
+    for arg in arguments {
+        match arg {
+            VersionDetailsArgument::String(x) => args.push(x.clone()),
+            VersionDetailsArgument::Rule(custom) if rules::allowed(&custom.rules, env) => {
+                match &custom.value {
+                    VersionDetailsArgumentValue::String(x) => args.push(x.clone()),
+                    VersionDetailsArgumentValue::List(x) => args.extend(x.iter().cloned()),
+                }
+            }
+            VersionDetailsArgument::Rule(_) => {}
+        }
+    }
+
+    args
+}